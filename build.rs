@@ -0,0 +1,38 @@
+use std::{env, fmt::Write, fs, path::Path};
+
+#[allow(dead_code)]
+#[path = "src/genotype.rs"]
+mod genotype;
+
+use genotype::Genotype;
+
+// writes the children of every ordered pair of genotypes, by base 3 index,
+// as one flat table. the children of pair a * 81 + b are the entries from
+// OFFSPRING_STARTS[pair] up to OFFSPRING_STARTS[pair + 1], each a child
+// index and the numerator of its chance out of 256.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/genotype.rs");
+
+    let mut starts = vec![0usize];
+    let mut children = Vec::new();
+    for parent1 in 0..81u8 {
+        for parent2 in 0..81u8 {
+            let parent1 = Genotype::from_base_3(parent1);
+            let parent2 = Genotype::from_base_3(parent2);
+            let mut row: Vec<(u8, usize)> = parent1
+                .breed(&parent2)
+                .map(|(numerator, child)| (child.get_base_3(), numerator))
+                .collect();
+            row.sort_unstable();
+            children.extend(row);
+            starts.push(children.len());
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "pub static OFFSPRING_STARTS: [u32; {}] = {:?};", starts.len(), starts).unwrap();
+    writeln!(out, "pub static OFFSPRING: [(u8, u16); {}] = {:?};", children.len(), children).unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("offspring.rs"), out).unwrap();
+}
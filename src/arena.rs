@@ -0,0 +1,192 @@
+use std::{collections::HashMap, mem::size_of, ops::Index};
+
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+
+use crate::{completion::Completion, cost::{CostModel, Objective}, distribution::{Distribution, DistributionRecord, Exact}, path::{Path, PathRecord}};
+
+// every distribution and path of a run is stored once, here, and referred
+// to by id. paths point at their parents by id too, so a search only moves
+// small copyable ids around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DistributionId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PathId(u32);
+
+impl DistributionId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl PathId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Default)]
+pub struct Arena {
+    distributions: Vec<Distribution>,
+    // distributions are the same only if every chance is.
+    distribution_ids: HashMap<Exact, DistributionId>,
+    paths: Vec<Path>,
+    // the children of every pair of distributions crossed so far, keyed on
+    // the pair in id order since a cross is the same either way round.
+    crosses: HashMap<(DistributionId, DistributionId), Vec<(f32, DistributionId)>>,
+    cross_children: usize,
+    // how many days of completion chances the paths hold between them.
+    completion_days: usize,
+}
+
+// every distribution and path in id order, for checkpoints. crosses aren't
+// kept, since they can be bred again when needed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ArenaRecord {
+    distributions: Vec<DistributionRecord>,
+    paths: Vec<PathRecord>,
+}
+
+// an arena written out as an ArenaRecord a record at a time, so saving a
+// large arena doesn't need a second copy of it.
+pub struct Recording<'a>(pub &'a Arena);
+
+// a sequence serialized from a fresh iterator.
+struct Each<F>(F);
+
+// something that refers into an arena, paired with it for serializing.
+pub struct InArena<'a, T>(pub &'a Arena, pub &'a T);
+
+impl Arena {
+    pub fn intern(&mut self, distribution: Distribution) -> DistributionId {
+        let next = DistributionId(self.distributions.len() as u32);
+        let id = *self.distribution_ids.entry(Exact(distribution)).or_insert(next);
+        if id == next {
+            self.distributions.push(distribution);
+        }
+        id
+    }
+
+    pub fn add(&mut self, path: Path) -> PathId {
+        self.completion_days += path.completion().map_or(0, |c| c.days());
+        self.paths.push(path);
+        PathId(self.paths.len() as u32 - 1)
+    }
+
+    // works out and keeps the chances of a path finishing on each day, for
+    // breeding from it. they're only needed by the percentile objective.
+    pub fn settle(&mut self, id: PathId, cost_model: &CostModel) {
+        if cost_model.objective == Objective::Mean || self[id].completion().is_some() {
+            return;
+        }
+        let completion = Completion::of_path(self, id, cost_model);
+        self.completion_days += completion.days();
+        self.paths[id.index()].set_completion(completion);
+    }
+
+    // the id of a distribution already interned.
+    pub fn id_of(&self, distribution: &Distribution) -> Option<DistributionId> {
+        self.distribution_ids.get(&Exact(*distribution)).copied()
+    }
+
+    pub fn distribution_count(&self) -> usize {
+        self.distributions.len()
+    }
+
+    #[cfg(test)]
+    pub fn distributions(&self) -> &[Distribution] {
+        &self.distributions
+    }
+
+    pub fn path_count(&self) -> usize {
+        self.paths.len()
+    }
+
+    #[cfg(test)]
+    pub fn completion_days(&self) -> usize {
+        self.completion_days
+    }
+
+    pub fn from_record(record: &ArenaRecord, cost_model: &CostModel) -> Result<Self, String> {
+        let mut arena = Self::default();
+        for (i, distribution) in record.distributions.iter().enumerate() {
+            let distribution = Distribution::from_record(distribution).map_err(|e| format!("distribution {}: {}", i, e))?;
+            if arena.intern(distribution).index() != i {
+                return Err(format!("distribution {} is listed twice", i));
+            }
+        }
+        for path in &record.paths {
+            let path = Path::from_record(&arena, path, cost_model)?;
+            arena.add(path);
+        }
+        Ok(arena)
+    }
+
+    // the children of a cross already worked out, most likely first.
+    pub fn crossed(&self, left: DistributionId, right: DistributionId) -> Option<&[(f32, DistributionId)]> {
+        self.crosses.get(&unordered(left, right)).map(|children| &children[..])
+    }
+
+    pub fn remember_cross(&mut self, left: DistributionId, right: DistributionId, children: Vec<(f32, Distribution)>) {
+        let children: Vec<_> = children.into_iter().map(|(probability, child)| (probability, self.intern(child))).collect();
+        self.cross_children += children.len();
+        self.crosses.insert(unordered(left, right), children);
+    }
+
+    // roughly how many bytes everything stored takes, not counting labels
+    // and purification tables.
+    pub fn memory(&self) -> usize {
+        self.paths.len() * size_of::<Path>()
+            + self.completion_days * size_of::<f64>()
+            + self.distributions.len() * (size_of::<Distribution>() + size_of::<(Exact, DistributionId)>())
+            + self.crosses.len() * size_of::<((DistributionId, DistributionId), Vec<(f32, DistributionId)>)>()
+            + self.cross_children * size_of::<(f32, DistributionId)>()
+    }
+
+    // the flower a path ends with.
+    pub fn target(&self, id: PathId) -> &Distribution {
+        &self[self[id].target]
+    }
+}
+
+impl Serialize for Recording<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let arena = self.0;
+        let mut record = serializer.serialize_struct("ArenaRecord", 2)?;
+        record.serialize_field("distributions", &Each(|| arena.distributions.iter().map(|d| d.to_record())))?;
+        record.serialize_field("paths", &Each(|| arena.paths.iter().map(|p| p.to_record())))?;
+        record.end()
+    }
+}
+
+impl<F, I> Serialize for Each<F>
+where
+    F: Fn() -> I,
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((self.0)())
+    }
+}
+
+pub fn unordered(left: DistributionId, right: DistributionId) -> (DistributionId, DistributionId) {
+    (left.min(right), left.max(right))
+}
+
+impl Index<DistributionId> for Arena {
+    type Output = Distribution;
+
+    fn index(&self, id: DistributionId) -> &Distribution {
+        &self.distributions[id.0 as usize]
+    }
+}
+
+impl Index<PathId> for Arena {
+    type Output = Path;
+
+    fn index(&self, id: PathId) -> &Path {
+        &self.paths[id.0 as usize]
+    }
+}
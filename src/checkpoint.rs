@@ -0,0 +1,121 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Write}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{arena::{Arena, ArenaRecord, PathId, Recording}, input::{LoadError, read_file}, planner::{Problem, Search, SearchOptions, SearchRecord}};
+
+// bumped whenever the format changes, so an old checkpoint is refused
+// rather than misread.
+const VERSION: u32 = 2;
+
+// everything a search needs to carry on where it left off: every path made
+// so far and the state of the search. resuming with the same run file and
+// search options gives the same plan as never stopping would. the arena
+// is written straight from the run's and read back as a record.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Checkpoint<A = ArenaRecord> {
+    version: u32,
+    arena: A,
+    search: SearchRecord,
+}
+
+impl Checkpoint {
+    // writes to a temporary file first, so a crash while saving leaves the
+    // last checkpoint as it was.
+    pub fn save(file: &str, arena: &Arena, search: &Search) -> io::Result<()> {
+        let checkpoint = Checkpoint {
+            version: VERSION,
+            arena: Recording(arena),
+            search: search.to_record(arena),
+        };
+        let temporary = format!("{}.tmp", file);
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        serde_json::to_writer(&mut writer, &checkpoint)?;
+        writer.flush()?;
+        fs::rename(&temporary, file)
+    }
+
+    pub fn load(file: &str) -> Result<Self, LoadError> {
+        let checkpoint: Self = read_file(file)?;
+        if checkpoint.version != VERSION {
+            let e = format!("checkpoint version {} can't be read, only version {}", checkpoint.version, VERSION);
+            return Err(LoadError::Invalid(file.to_string(), e));
+        }
+        Ok(checkpoint)
+    }
+
+    // swaps the run's arena, which only holds its sources, for the
+    // checkpoint's and gives the search to carry on with. the checkpoint
+    // must start with the same sources.
+    pub fn resume<'a>(
+        &self,
+        file: &str,
+        arena: &mut Arena,
+        sources: &[PathId],
+        problem: Problem<'a>,
+        options: SearchOptions<'a>,
+    ) -> Result<Search<'a>, LoadError> {
+        let invalid = |e: String| LoadError::Invalid(file.to_string(), e);
+        let loaded = Arena::from_record(&self.arena, problem.cost_model).map_err(invalid)?;
+        for &source in sources {
+            let same = source.index() < loaded.path_count()
+                && loaded[source].label() == arena[source].label()
+                && loaded.id_of(arena.target(source)) == Some(loaded[source].target);
+            if !same {
+                return Err(invalid("the checkpoint was made from a different run file".to_string()));
+            }
+        }
+        let search = Search::from_record(&loaded, sources, problem, options, &self.search).map_err(invalid)?;
+        *arena = loaded;
+        Ok(search)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{arena::InArena, distribution_helpers::BreedContext, input::Run, planner::SearchLimits};
+
+    const BLUE_ROSE: &str = r#"{
+        "species": "Rose",
+        "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+        "target": { "color": "Blue" }
+    }"#;
+
+    // a search saved, loaded and resumed gives the same plan as one that
+    // never stopped.
+    #[test]
+    fn resumes_to_the_same_plan() {
+        let mut run = Run::from_json(BLUE_ROSE);
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let mut search = Search::new(&run.arena, &run.sources, problem, SearchOptions::default());
+        let path = search.run(&mut run.arena, |_, _| {}).unwrap();
+        let uninterrupted = serde_json::to_string(&InArena(&run.arena, &path)).unwrap();
+
+        let mut run = Run::from_json(BLUE_ROSE);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let limits = SearchLimits { max_expanded: Some(200), ..SearchLimits::default() };
+        let mut search = Search::new(&run.arena, &run.sources, problem, SearchOptions { limits, ..SearchOptions::default() });
+        search.run(&mut run.arena, |_, _| {});
+        assert!(search.stats.stopped.is_some());
+        let file = std::env::temp_dir().join(format!("hanami-checkpoint-{}.json", std::process::id()));
+        let file = file.to_str().unwrap();
+        Checkpoint::save(file, &run.arena, &search).unwrap();
+
+        let mut resumed_run = Run::from_json(BLUE_ROSE);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let checkpoint = Checkpoint::load(file).unwrap();
+        fs::remove_file(file).unwrap();
+        let mut search = checkpoint.resume(file, &mut resumed_run.arena, &resumed_run.sources, problem, SearchOptions::default()).unwrap();
+        // every distribution must load with the chances it was saved with,
+        // or new crosses give the same flower a second id.
+        assert_eq!(resumed_run.arena.distribution_count(), run.arena.distribution_count());
+        for (i, distribution) in run.arena.distributions().iter().enumerate() {
+            assert_eq!(resumed_run.arena.id_of(distribution).map(|id| id.index()), Some(i));
+        }
+        let path = search.run(&mut resumed_run.arena, |_, _| {}).unwrap();
+        assert_eq!(serde_json::to_string(&InArena(&resumed_run.arena, &path)).unwrap(), uninterrupted);
+    }
+}
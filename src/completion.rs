@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{arena::{Arena, PathId}, cost::CostModel};
+
+// once this little probability is left the distribution is cut off.
+const TAIL: f64 = 1e-6;
+
+// the distribution of the day a plan is finished on, assuming every cross
+// waits a geometric number of days and separate branches run side by side.
+// probability[d] is the chance of finishing on day d.
+#[derive(Clone, Debug)]
+pub struct Completion {
+    probability: Vec<f64>,
+}
+
+impl Completion {
+    // the chances as bit patterns, so a checkpoint gives them back exactly.
+    pub fn to_bits(&self) -> Vec<u64> {
+        self.probability.iter().map(|p| p.to_bits()).collect()
+    }
+
+    pub fn from_bits(bits: &[u64]) -> Self {
+        Self {
+            probability: bits.iter().map(|&b| f64::from_bits(b)).collect(),
+        }
+    }
+
+    // how many days it has a chance for.
+    pub fn days(&self) -> usize {
+        self.probability.len()
+    }
+
+    // a flower that's already owned.
+    pub fn owned() -> Self {
+        Self {
+            probability: vec![1f64],
+        }
+    }
+
+    // when both parents of a cross are ready. a flower and its own clone
+    // aren't independent: the clone is always ready last.
+    pub fn parents(arena: &Arena, left: PathId, right: PathId, left_completion: &Self, right_completion: &Self) -> Self {
+        let cloned = |clone: PathId, original: PathId| arena[clone].cloned_from() == Some(original);
+        if left == right || cloned(left, right) {
+            left_completion.clone()
+        } else if cloned(right, left) {
+            right_completion.clone()
+        } else {
+            left_completion.max(right_completion)
+        }
+    }
+
+    fn max(&self, other: &Self) -> Self {
+        let len = self.probability.len().max(other.probability.len());
+        let (mut a, mut b) = (0f64, 0f64);
+        let mut previous = 0f64;
+        let mut probability = Vec::with_capacity(len);
+        for d in 0..len {
+            a += self.probability.get(d).copied().unwrap_or(0f64);
+            b += other.probability.get(d).copied().unwrap_or(0f64);
+            let cumulative = a * b;
+            probability.push(cumulative - previous);
+            previous = cumulative;
+        }
+        Self { probability }
+    }
+
+    // adds a geometric wait of at least one day. with f the current
+    // distribution and g the new one, g(d) = (1 - q) g(d - 1) + q f(d - 1).
+    pub fn wait(&self, daily_chance: f64) -> Self {
+        Self {
+            probability: self.waited(daily_chance).collect(),
+        }
+    }
+
+    // the percentile the wait would have, without keeping its chances. a
+    // cross that is unlikely to come up runs out to many days, so only the
+    // flowers actually expanded keep theirs. past the last day the parents
+    // could be ready on, what's left of the wait shrinks by 1 - q a day, so
+    // the rest is worked out in closed form.
+    pub fn wait_percentile(&self, daily_chance: f64, fraction: f64) -> usize {
+        let q = daily_chance.clamp(f64::MIN_POSITIVE, 1f64);
+        let days = self.probability.len();
+        let mut cumulative = 0f64;
+        for (d, p) in self.waited(q).take(days + 1).enumerate() {
+            cumulative += p;
+            if cumulative >= fraction {
+                return d;
+            }
+        }
+        let total: f64 = self.probability.iter().sum();
+        let left = total - cumulative;
+        if q >= 1f64 || total - fraction <= 0f64 || left <= 0f64 {
+            return percentile(self.waited(q), fraction);
+        }
+        let more = ((total - fraction) / left).ln() / (-q).ln_1p();
+        days + more.ceil().max(1f64) as usize
+    }
+
+    fn waited(&self, daily_chance: f64) -> impl Iterator<Item = f64> + '_ {
+        let q = daily_chance.clamp(f64::MIN_POSITIVE, 1f64);
+        let total: f64 = self.probability.iter().sum();
+        let mut previous = 0f64;
+        let mut cumulative = 0f64;
+        let mut d = 0;
+        std::iter::from_fn(move || {
+            if d > 0 && cumulative >= total - TAIL {
+                return None;
+            }
+            let before = match d {
+                0 => 0f64,
+                _ => self.probability.get(d - 1).copied().unwrap_or(0f64),
+            };
+            let p = (1f64 - q) * previous + q * before;
+            previous = p;
+            cumulative += p;
+            d += 1;
+            Some(p)
+        })
+    }
+
+    // a purification loop isn't a single geometric wait, but is treated as
+    // one with the same expected days.
+    pub fn purify(&self, days: f32) -> Self {
+        self.wait(1f64 / days.max(1f32) as f64)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.probability.iter().enumerate().map(|(d, p)| d as f64 * p).sum()
+    }
+
+    // the first day by which the plan is done with at least this chance.
+    pub fn percentile(&self, fraction: f64) -> usize {
+        percentile(self.probability.iter().copied(), fraction)
+    }
+
+    // works out the distribution for a whole plan, computing shared steps
+    // once.
+    pub fn of_path(arena: &Arena, id: PathId, cost_model: &CostModel) -> Self {
+        let mut known = HashMap::new();
+        Self::of_path_cached(arena, id, cost_model, &mut known)
+    }
+
+    fn of_path_cached(arena: &Arena, id: PathId, cost_model: &CostModel, known: &mut HashMap<PathId, Self>) -> Self {
+        let path = &arena[id];
+        if let Some(completion) = path.completion() {
+            return completion.clone();
+        }
+        if let Some(completion) = known.get(&id) {
+            return completion.clone();
+        }
+        let completion = match (path.parents(), path.probability(), path.cloned_from()) {
+            (Some((left, right)), Some(probability), _) => {
+                let left_completion = Self::of_path_cached(arena, left, cost_model, known);
+                let right_completion = Self::of_path_cached(arena, right, cost_model, known);
+                let parents = Self::parents(arena, left, right, &left_completion, &right_completion);
+                parents.wait((cost_model.breed_rate * probability) as f64)
+            }
+            (_, _, Some(original)) => {
+                Self::of_path_cached(arena, original, cost_model, known).wait(cost_model.clone_rate as f64)
+            }
+            _ => match path.purified_from() {
+                Some((original, _)) => {
+                    let days = path.expected_time - arena[original].expected_time;
+                    Self::of_path_cached(arena, original, cost_model, known).purify(days)
+                }
+                None => Self::owned(),
+            },
+        };
+        known.insert(id, completion.clone());
+        completion
+    }
+}
+
+fn percentile(probability: impl Iterator<Item = f64>, fraction: f64) -> usize {
+    let mut cumulative = 0f64;
+    let mut last = 0;
+    for (d, p) in probability.enumerate() {
+        cumulative += p;
+        if cumulative >= fraction {
+            return d;
+        }
+        last = d;
+    }
+    last
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub expected_time: f32,
+    pub mean: f64,
+    pub percentile_50: usize,
+    pub percentile_90: usize,
+    pub percentile_99: usize,
+}
+
+impl Report {
+    pub fn new(arena: &Arena, id: PathId, cost_model: &CostModel) -> Self {
+        let completion = Completion::of_path(arena, id, cost_model);
+        Self {
+            expected_time: arena[id].expected_time,
+            mean: completion.mean(),
+            percentile_50: completion.percentile(0.5),
+            percentile_90: completion.percentile(0.9),
+            percentile_99: completion.percentile(0.99),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the closed form tail has to agree with waiting day by day.
+    #[test]
+    fn wait_percentile_matches_the_wait() {
+        let owned = Completion::owned();
+        let parents = [owned.clone(), owned.wait(0.25), owned.wait(0.5).wait(1f64 / 64f64), owned.wait(0.1).max(&owned.wait(0.75))];
+        for parent in &parents {
+            for q in [1f64, 0.5, 1f64 / 7f64, 1f64 / 300f64] {
+                for fraction in [0.5, 0.9, 0.99] {
+                    assert_eq!(parent.wait_percentile(q, fraction), parent.wait(q).percentile(fraction), "{} {}", q, fraction);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+
+use crate::{arena::Arena, distribution::Distribution, flowers::FlowerColor, path::Path};
+
+// restrictions the search applies to every path before it is queued.
+// an empty object (or no constraints at all) allows everything.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Constraints {
+    // colors that may not appear anywhere in a plan, including intermediates.
+    #[serde(default)]
+    pub excluded_colors: Vec<FlowerColor>,
+
+    // the deepest breeding chain allowed, counting sources as generation 0.
+    pub max_generations: Option<usize>,
+
+    // labels of the only starting flowers a plan may use.
+    pub allowed_sources: Option<Vec<String>>,
+
+    // the most genotypes a flower in the plan may possibly have.
+    pub max_genotypes: Option<usize>,
+}
+
+impl Constraints {
+    pub fn allows(&self, arena: &Arena, path: &Path) -> bool {
+        if !self.allows_distribution(&arena[path.target]) {
+            return false;
+        }
+        if let Some(max_generations) = self.max_generations {
+            if path.generation > max_generations {
+                return false;
+            }
+        }
+        if let (Some(allowed), Some(label)) = (&self.allowed_sources, path.label()) {
+            if !allowed.iter().any(|a| a == label) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // the checks that only depend on the flower itself, not how it was bred.
+    pub fn allows_distribution(&self, distribution: &Distribution) -> bool {
+        if self.excluded_colors.contains(&distribution.flower_color) {
+            return false;
+        }
+        if let Some(max_genotypes) = self.max_genotypes {
+            if distribution.genotype_count() > max_genotypes {
+                return false;
+            }
+        }
+        true
+    }
+}
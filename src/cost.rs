@@ -0,0 +1,66 @@
+use serde::Deserialize;
+
+// how many days a step of a plan is expected to take.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CostModel {
+    // the chance a breeding pair produces any child on a given day.
+    #[serde(default = "default_breed_rate")]
+    pub breed_rate: f32,
+
+    // the chance a flower with no partner spreads a copy of itself on a
+    // given day.
+    #[serde(default = "default_breed_rate")]
+    pub clone_rate: f32,
+
+    // how sure a purification has to be that a flower is the wanted
+    // genotype before it counts as that genotype.
+    #[serde(default = "default_purify_confidence")]
+    pub purify_confidence: f32,
+
+    // what the search minimizes.
+    #[serde(default)]
+    pub objective: Objective,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Objective {
+    // the expected days until the plan is done.
+    #[default]
+    Mean,
+    // the days until the plan is done with at least this chance, eg 0.9.
+    Percentile(f32),
+}
+
+fn default_breed_rate() -> f32 {
+    1f32
+}
+
+fn default_purify_confidence() -> f32 {
+    0.99
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            breed_rate: default_breed_rate(),
+            clone_rate: default_breed_rate(),
+            purify_confidence: default_purify_confidence(),
+            objective: Objective::default(),
+        }
+    }
+}
+
+impl CostModel {
+    // expected days until a pair produces a child that has the given
+    // chance of being the wanted color.
+    pub fn breed_days(&self, probability: f32) -> f32 {
+        1f32 / (self.breed_rate * probability)
+    }
+
+    // expected days until a flower spreads a copy of itself.
+    pub fn clone_days(&self) -> f32 {
+        1f32 / self.clone_rate
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Serialize;
+
+use crate::{arena::Arena, distribution::Distribution, flowers::FlowerColor, path::{Step, StepSource}};
+
+// a flat form of a plan where every distinct step is listed once and
+// refers to its parents by id, so shared ancestors aren't repeated.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dag<'a> {
+    pub root: usize,
+    pub nodes: Vec<DagNode<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DagNode<'a> {
+    pub id: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<&'a str>,
+    pub color: FlowerColor,
+    pub expected_time: f32,
+    pub genotypes: &'a Distribution,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parents: Option<(usize, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone_of: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purified_from: Option<usize>,
+}
+
+impl<'a> Dag<'a> {
+    pub fn new(arena: &'a Arena, steps: &[Step]) -> Self {
+        let nodes = steps.iter().enumerate().map(|(id, &(path, ref source))| DagNode {
+            id,
+            label: arena[path].label(),
+            color: arena.target(path).flower_color,
+            expected_time: arena[path].expected_time,
+            genotypes: arena.target(path),
+            parents: match *source {
+                StepSource::Breed(left, right) => Some((left, right)),
+                _ => None,
+            },
+            clone_of: match *source {
+                StepSource::Clone(original) => Some(original),
+                _ => None,
+            },
+            purified_from: match *source {
+                StepSource::Purify(original) => Some(original),
+                _ => None,
+            },
+        });
+
+        Self {
+            root: steps.len() - 1,
+            nodes: nodes.collect(),
+        }
+    }
+}
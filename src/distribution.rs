@@ -1,24 +1,56 @@
 use std::hash::Hash;
 
-use crate::{distribution_helpers::breed, flowers::FlowerColor, flowers::FlowerType, flowers::get_color, genotype::Genotype};
+use crate::{distribution_helpers::{BreedContext, breed, breed_mixture}, flowers::{FlowerColor, FlowerType, GOLD_CHANCE, can_turn_gold, color_list, genotype_color, get_color}, genotype::Genotype, input::parse_genotype};
 
-use sprs::CsVec;
-use serde::ser::{Serialize, SerializeSeq};
+use serde::{Deserialize, Serialize, ser::SerializeSeq};
 
-#[derive(Clone)]
+// the most genotypes that show any one color, which is 18 for white and
+// yellow roses. every distribution is of a single color.
+pub const MAX_GENOTYPES: usize = 18;
+
+#[derive(Clone, Copy)]
 pub struct Distribution {
     pub flower_type:  FlowerType,
     pub flower_color: FlowerColor,
-    pub inner:        CsVec<f32>,
+    // bit i is set when the genotype with base 3 index i is possible.
+    support:          u128,
+    // the chance of each possible genotype in index order, as numerators over
+    // their sum. they stay exact unless they grow too big to store.
+    numerators:       [u32; MAX_GENOTYPES],
 }
 
 impl Hash for Distribution {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.flower_type.hash(state);
-        self.inner.indices().hash(state);
-        // for d in self.inner.data().iter() {
-        //     ((1f32 / d).round() as u8).hash(state);
-        // }
+        self.support.hash(state);
+    }
+}
+
+// a distribution as stored in a checkpoint, with its exact numerators.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DistributionRecord {
+    flower_type: FlowerType,
+    color: FlowerColor,
+    genotypes: Vec<(String, u32)>,
+}
+
+// a distribution compared on every chance, not just on which genotypes
+// are possible.
+#[derive(Clone, Copy)]
+pub struct Exact(pub Distribution);
+
+impl Hash for Exact {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.0.numerators.hash(state);
+    }
+}
+
+impl Eq for Exact { }
+impl PartialEq for Exact {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.0.numerators == other.0.numerators
     }
 }
 
@@ -26,8 +58,8 @@ impl Serialize for Distribution {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer {
-        let mut list = serializer.serialize_seq(Some(self.inner.indices().len()))?;
-        for (g, _) in self.inner.iter() {
+        let mut list = serializer.serialize_seq(Some(self.genotype_count()))?;
+        for (g, _) in self.numerators() {
             let genotype = Genotype::from_base_3(g as u8);
             let genotype = format!("{:?}", &genotype);
             list.serialize_element(&genotype)?;
@@ -45,31 +77,186 @@ impl PartialEq for Distribution {
         if self.flower_color != other.flower_color {
             return false;
         }
-        if self.inner.indices() != other.inner.indices() {
+        if self.support != other.support {
             return false;
         }
-        return true;
+        true
     }
 }
 
 impl Distribution {
     pub fn new(flower_type: FlowerType, genotype: Genotype) -> Self {
         let index = dist_index_from_genotype(genotype);
-        let inner = CsVec::new(81, vec![index], vec![1.0]);
         let flower_color = get_color(&flower_type, genotype);
+        Self::from_weights(flower_type, flower_color, &[(index, 1)])
+    }
+
+    // a distribution over the genotypes with the given base 3 indices, in
+    // increasing order, with chances in proportion to the weights. weights
+    // too big to store are scaled down, which is the only time the chances
+    // stop being exact.
+    pub fn from_weights(flower_type: FlowerType, flower_color: FlowerColor, weights: &[(usize, u128)]) -> Self {
+        assert!(weights.len() <= MAX_GENOTYPES);
+        // numerators are matched to support bits by position.
+        debug_assert!(weights.windows(2).all(|w| w[0].0 < w[1].0), "genotype indices must be increasing");
+        let divisor = weights.iter().fold(0, |d, &(_, w)| gcd(d, w)).max(1);
+        let largest = weights.iter().map(|&(_, w)| w / divisor).max().unwrap_or(0);
+        let mut support = 0u128;
+        let mut numerators = [0u32; MAX_GENOTYPES];
+        for (slot, &(index, weight)) in weights.iter().enumerate() {
+            let weight = weight / divisor;
+            numerators[slot] = if largest > u32::MAX as u128 {
+                (weight * u32::MAX as u128 / largest).max(1) as u32
+            } else {
+                weight as u32
+            };
+            support |= 1 << index;
+        }
 
         Self {
             flower_type,
             flower_color,
-            inner,
+            support,
+            numerators,
+        }
+    }
+
+    // the same, from chances that needn't be exact.
+    pub fn from_chances(flower_type: FlowerType, flower_color: FlowerColor, chances: &[(usize, f64)]) -> Self {
+        let largest = chances.iter().map(|&(_, p)| p).fold(0f64, f64::max);
+        let weights: Vec<_> = chances
+            .iter()
+            .map(|&(index, p)| (index, ((p / largest * u32::MAX as f64).round() as u128).max(1)))
+            .collect();
+        Self::from_weights(flower_type, flower_color, &weights)
+    }
+
+    // an even spread over every genotype of the species that shows this color,
+    // for flowers whose genotype is unknown.
+    pub fn from_color(flower_type: FlowerType, flower_color: FlowerColor) -> Option<Self> {
+        let shown = genotype_color(&flower_type, flower_color);
+        let indices: Vec<usize> = color_list(&flower_type)
+            .iter()
+            .enumerate()
+            .filter(|(_, &c)| c == shown)
+            .map(|(i, _)| i)
+            .collect();
+        if indices.is_empty() {
+            return None;
+        }
+        let weights: Vec<_> = indices.into_iter().map(|i| (i, 1)).collect();
+        Some(Self::from_weights(flower_type, flower_color, &weights))
+    }
+
+    pub fn breed(&self, other: &Self, context: &BreedContext) -> Vec<(f32, Self)> {
+        breed(context, self, other)
+    }
+
+    // the gold children of two black roses watered with the golden watering
+    // can, or None unless both can only be black roses. the black children
+    // that don't turn gold are split off like any other unwanted color.
+    pub fn breed_golden(&self, other: &Self, context: &BreedContext) -> Option<(f32, Self)> {
+        let black = |d: &Self| d.genotypes().all(|(g, _)| can_turn_gold(&d.flower_type, g));
+        if !black(self) || !black(other) {
+            return None;
+        }
+        let (probability, children) = self.breed(other, context).into_iter().find(|(_, d)| d.flower_color == FlowerColor::Black)?;
+        Some((probability * GOLD_CHANCE, Self { flower_color: FlowerColor::Gold, ..children }))
+    }
+
+    // breeding with one of several neighbors, chosen at random by weight.
+    pub fn breed_mixture(&self, partners: &[(f32, &Self)], context: &BreedContext) -> Vec<(f32, Self)> {
+        breed_mixture(context, self, partners)
+    }
+
+    pub fn genotype_count(&self) -> usize {
+        self.support.count_ones() as usize
+    }
+
+    pub fn genotypes(&self) -> impl Iterator<Item = (Genotype, f32)> + '_ {
+        let total = self.total() as f64;
+        self.numerators().map(move |(i, n)| (genotype_from_dist_index(i), (n as f64 / total) as f32))
+    }
+
+    // the base 3 index and numerator of every possible genotype.
+    pub fn numerators(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        let mut support = self.support;
+        let indices = std::iter::from_fn(move || {
+            if support == 0 {
+                return None;
+            }
+            let index = support.trailing_zeros() as usize;
+            support &= support - 1;
+            Some(index)
+        });
+        indices.zip(self.numerators.iter().copied())
+    }
+
+    // whether every genotype this could be, the other could be too.
+    pub fn is_within(&self, other: &Self) -> bool {
+        self.support & !other.support == 0
+    }
+
+    // the chance of being one of the genotypes, as a numerator over total().
+    pub fn numerator_of(&self, genotypes: &[Genotype]) -> u64 {
+        self.numerators()
+            .filter(|&(i, _)| genotypes.contains(&genotype_from_dist_index(i)))
+            .map(|(_, n)| n as u64)
+            .sum()
+    }
+
+    pub fn to_record(self) -> DistributionRecord {
+        DistributionRecord {
+            flower_type: self.flower_type,
+            color: self.flower_color,
+            genotypes: self.numerators().map(|(i, n)| (format!("{:?}", genotype_from_dist_index(i)), n)).collect(),
         }
     }
 
-    pub fn breed(&self, other: &Self) -> Vec<(f32, Self)> {
-        breed(self, other)
+    // the numerators are restored as they were, not reduced again, since a
+    // distribution scaled down to fit can still share a factor.
+    pub fn from_record(record: &DistributionRecord) -> Result<Self, String> {
+        let (flower_type, flower_color) = (record.flower_type, record.color);
+        let mut weights = Vec::new();
+        for (genotype, numerator) in &record.genotypes {
+            let parsed = parse_genotype(&flower_type, genotype)?;
+            if get_color(&flower_type, parsed) != genotype_color(&flower_type, flower_color) {
+                return Err(format!("{} isn't a {:?} {:?}", genotype, flower_color, flower_type));
+            }
+            weights.push((dist_index_from_genotype(parsed), *numerator));
+        }
+        weights.sort_unstable();
+        if weights.is_empty() || weights.windows(2).any(|w| w[0].0 == w[1].0) || weights.iter().any(|&(_, n)| n == 0) {
+            return Err("a distribution needs distinct genotypes with nonzero numerators".to_string());
+        }
+        if weights.len() > MAX_GENOTYPES {
+            return Err(format!("a distribution can have at most {} genotypes", MAX_GENOTYPES));
+        }
+
+        let mut support = 0u128;
+        let mut numerators = [0u32; MAX_GENOTYPES];
+        for (slot, &(index, numerator)) in weights.iter().enumerate() {
+            numerators[slot] = numerator;
+            support |= 1 << index;
+        }
+        Ok(Self {
+            flower_type,
+            flower_color,
+            support,
+            numerators,
+        })
+    }
+
+    // what the numerators are out of.
+    pub fn total(&self) -> u64 {
+        self.numerators.iter().map(|&n| n as u64).sum()
     }
 }
 
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
 pub fn dist_index_from_genotype(genotype: Genotype) -> usize {
     genotype.get_base_3() as usize
 }
@@ -84,4 +271,101 @@ pub fn genotype_from_dist_index(index: usize) -> Genotype {
 //     fn eq(&self, other: &Self) -> bool {
 //         todo!()
 //     }
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flowers::seeds;
+
+    // scaled down numerators can share a factor, which loading must keep.
+    #[test]
+    fn records_keep_scaled_numerators() {
+        let red = Distribution::from_color(FlowerType::Rose, FlowerColor::Red).unwrap();
+        let mut indices = red.numerators().map(|(i, _)| i);
+        let (first, second) = (indices.next().unwrap(), indices.next().unwrap());
+        let scaled = Distribution::from_weights(FlowerType::Rose, FlowerColor::Red, &[(first, 549_755_814_273), (second, 1_099_511_627_777)]);
+        assert!(scaled.numerators().all(|(_, n)| n % 3 == 0));
+
+        let loaded = Distribution::from_record(&scaled.to_record()).unwrap();
+        assert!(Exact(loaded) == Exact(scaled));
+    }
+
+    #[test]
+    fn records_need_genotypes_of_their_color() {
+        let mut record = Distribution::from_color(FlowerType::Rose, FlowerColor::Red).unwrap().to_record();
+        record.color = FlowerColor::White;
+        assert!(Distribution::from_record(&record).is_err());
+        record.genotypes.clear();
+        assert!(Distribution::from_record(&record).is_err());
+    }
+
+    // the chance of a color, and of each of its genotypes within it.
+    type Child = (FlowerColor, f64, Vec<(Genotype, f64)>);
+
+    // the children of two flowers worked out genotype by genotype.
+    fn breed_directly(a: &Distribution, b: &Distribution) -> Vec<Child> {
+        let mut children: Vec<Child> = Vec::new();
+        for (g1, p1) in a.genotypes() {
+            for (g2, p2) in b.genotypes() {
+                for (numerator, child) in g1.breed(&g2) {
+                    let chance = p1 as f64 * p2 as f64 * numerator as f64 / 256f64;
+                    let color = get_color(&a.flower_type, child);
+                    let index = match children.iter().position(|(c, _, _)| *c == color) {
+                        Some(index) => index,
+                        None => {
+                            children.push((color, 0f64, Vec::new()));
+                            children.len() - 1
+                        }
+                    };
+                    let (_, total, genotypes) = &mut children[index];
+                    *total += chance;
+                    match genotypes.iter_mut().find(|(g, _)| *g == child) {
+                        Some((_, p)) => *p += chance,
+                        None => genotypes.push((child, chance)),
+                    }
+                }
+            }
+        }
+        for (_, total, genotypes) in &mut children {
+            for (_, p) in genotypes.iter_mut() {
+                *p /= *total;
+            }
+        }
+        children
+    }
+
+    // the offspring tables and exact numerators have to agree with crossing
+    // the genotypes one pair at a time.
+    #[test]
+    fn breeds_like_the_genotypes() {
+        let species = [FlowerType::Rose, FlowerType::Cosmo, FlowerType::Lily, FlowerType::Pansy, FlowerType::Tulip, FlowerType::Hyacinth, FlowerType::Mum, FlowerType::Windflower];
+        for flower_type in species {
+            let context = BreedContext::new(flower_type);
+            let mut flowers: Vec<_> = seeds(&flower_type).iter().map(|&(_, g)| Distribution::new(flower_type, g)).collect();
+            let seed_count = flowers.len();
+            for i in 0..seed_count {
+                for j in i..seed_count {
+                    let children = flowers[i].breed(&flowers[j], &context);
+                    flowers.extend(children.into_iter().map(|(_, child)| child));
+                }
+            }
+
+            for a in &flowers {
+                for b in &flowers {
+                    let expected = breed_directly(a, b);
+                    let children = a.breed(b, &context);
+                    assert_eq!(children.len(), expected.len());
+                    for (probability, child) in children {
+                        let (_, total, genotypes) = expected.iter().find(|(c, _, _)| *c == child.flower_color).unwrap();
+                        assert!((probability as f64 - total).abs() < 1e-6);
+                        assert_eq!(child.genotype_count(), genotypes.len());
+                        for (genotype, chance) in child.genotypes() {
+                            let (_, p) = genotypes.iter().find(|(g, _)| *g == genotype).unwrap();
+                            assert!((chance as f64 - p).abs() < 1e-6, "{:?} {} {}", genotype, chance, p);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,125 +1,105 @@
-use std::{collections::HashMap, sync::RwLock};
-
-use sprs::{CsMat, CsVec};
-
-use crate::{distribution::Distribution, distribution::dist_index_from_genotype, distribution::genotype_from_dist_index, flowers::{COSMOS_LIST, FlowerColor, FlowerType, HYACINTHS_LIST, LILIES_LIST, MUMS_LIST, PANSIES_LIST, ROSES_LIST, TULIPS_LIST, WINDFLOWERS_LIST}};
-
-
-lazy_static! {
-    static ref COLOR_FILTERS: RwLock<HashMap<(FlowerType, FlowerColor), CsMat<f32>>> = RwLock::new(HashMap::new());
-    static ref GENOTYPE_MAPS: RwLock<HashMap<FlowerType, (Vec<FlowerColor>, CsMat<f32>)>> = RwLock::new(HashMap::new());
-
-    static ref BREED_MATRIX: CsMat<f32> = {
-        let mut breed_mat = CsMat::<f32>::zero((81, 81 * 81)).transpose_into();
-
-        for parent1 in 0..81 {
-            for parent2 in 0..81 {
-                let parent1_genotype = genotype_from_dist_index(parent1);
-                let parent2_genotype = genotype_from_dist_index(parent2);
-                let parent_index = parent1 * 81 + parent2;
-                for (probability_numerator, child_genotype) in parent1_genotype.breed(&parent2_genotype) {
-                    let probability = probability_numerator as f32 / 256f32;
-                    let child_index = dist_index_from_genotype(child_genotype);
+use crate::{distribution::Distribution, flowers::{FlowerColor, FlowerType, color_list}};
+
+// the children of every pair of genotypes, generated by build.rs.
+include!(concat!(env!("OUT_DIR"), "/offspring.rs"));
+
+// what breeding one species needs on top of the offspring tables, built once
+// up front and passed around by reference.
+pub struct BreedContext {
+    pub flower_type: FlowerType,
+    // the colors of the species in the order they first show up, and which
+    // of them each genotype shows.
+    colors: Vec<FlowerColor>,
+    color_of: Vec<usize>,
+}
 
-                    breed_mat.insert(parent_index, child_index, probability);
+impl BreedContext {
+    pub fn new(flower_type: FlowerType) -> Self {
+        let mut colors = Vec::new();
+        let mut color_of = Vec::new();
+        for &color in color_list(&flower_type) {
+            let col = match colors.iter().position(|x| x == &color) {
+                Some(col) => col,
+                None => {
+                    colors.push(color);
+                    colors.len() - 1
                 }
-            }
+            };
+            color_of.push(col);
         }
 
-        breed_mat
-    };
-}
-
-fn process_flower(flower_type: &FlowerType) {
-    let genotype_maps = GENOTYPE_MAPS.read().unwrap();
-    if genotype_maps.contains_key(flower_type) {
-        return;
-    }
-    std::mem::drop(genotype_maps);
-
-    let map_slice: &[FlowerColor] = match flower_type {
-        FlowerType::Rose => &ROSES_LIST,
-        FlowerType::Cosmo => &COSMOS_LIST,
-        FlowerType::Lily => &LILIES_LIST,
-        FlowerType::Pansy => &PANSIES_LIST,
-        FlowerType::Tulip => &HYACINTHS_LIST,
-        FlowerType::Hyacinth => &TULIPS_LIST,
-        FlowerType::Mum => &MUMS_LIST,
-        FlowerType::Windflower => &WINDFLOWERS_LIST,
-    };
-    let mut new_genotype_map_mat = CsMat::zero((0, map_slice.len())).to_csc();
-    let mut new_genotype_map_vec = Vec::new();
-    let mut new_color_filters = HashMap::new();
-    for (row, &color) in map_slice.iter().enumerate() {
-        let col = new_genotype_map_vec.iter().rposition(|x| x == &color);
-        let col = match col {
-            Some(col) => col,
-            None => {
-                new_genotype_map_vec.push(color);
-                new_color_filters.insert((*flower_type, color), CsMat::zero((map_slice.len(), map_slice.len())));
-                new_genotype_map_vec.len() - 1
-            }
-        };
-        new_genotype_map_mat.insert(row, col, 1f32);
-        new_color_filters.get_mut(&(*flower_type, color)).unwrap().insert(row, row, 1f32);
+        Self {
+            flower_type,
+            colors,
+            color_of,
+        }
     }
-    let mut genotype_maps = GENOTYPE_MAPS.write().unwrap();
-    genotype_maps.insert(*flower_type, (new_genotype_map_vec, new_genotype_map_mat));
-    let mut color_filters = COLOR_FILTERS.write().unwrap();
-    color_filters.extend(new_color_filters.into_iter());
 }
 
-pub fn breed(a: &Distribution, b: &Distribution) -> Vec<(f32, Distribution)> {
-    if a.flower_type != b.flower_type {
+pub fn breed(context: &BreedContext, a: &Distribution, b: &Distribution) -> Vec<(f32, Distribution)> {
+    if a.flower_type != context.flower_type || b.flower_type != context.flower_type {
         panic!()
     }
-    let spread_mat = &(a.inner.col_view::<usize>()) * &b.inner.row_view();
-    let spread_vec = as_one_row(spread_mat);
-    let breed_mat = &BREED_MATRIX;
-    let breed_dist = &spread_vec * &breed_mat;
-
-    let flower_type = a.flower_type;
-    process_flower(&flower_type);
-    let genotype_maps = GENOTYPE_MAPS.read().unwrap();
-    let color_filters = COLOR_FILTERS.read().unwrap();
-    
-    let (color_vec, mat) = genotype_maps.get(&flower_type).unwrap();
-    let mut color_dist = vec![0f32; mat.cols()];
-    (&breed_dist * &mat).scatter(&mut color_dist);
+    let (children, total) = offspring(a, b);
+    let chances = children.map(|weight| weight as f64 / total as f64);
+    split_by_color(context, &chances, |color, weights| {
+        let weights: Vec<_> = weights.iter().map(|&(index, _)| (index, children[index])).collect();
+        Distribution::from_weights(context.flower_type, color, &weights)
+    })
+}
 
-    
-    let mut outputs = Vec::new();
-    for (color, &probability) in color_vec.iter().zip(color_dist.iter()) {
-        if probability == 0f32 {
-            continue;
+// the children of a flower placed next to several partners, where each
+// child comes from one of the pairings picked at random by weight.
+pub fn breed_mixture(context: &BreedContext, a: &Distribution, partners: &[(f32, &Distribution)]) -> Vec<(f32, Distribution)> {
+    let total_weight: f32 = partners.iter().map(|(weight, _)| weight).sum();
+    let mut chances = [0f64; 81];
+    for (weight, b) in partners {
+        if a.flower_type != context.flower_type || b.flower_type != context.flower_type {
+            panic!()
+        }
+        let (children, total) = offspring(a, b);
+        let share = (weight / total_weight) as f64;
+        for (chance, weight) in chances.iter_mut().zip(children) {
+            *chance += share * weight as f64 / total as f64;
         }
-        let mut filtered = &breed_dist * color_filters.get(&(flower_type, *color)).unwrap();
-        filtered.map_inplace(|x| x / probability);
-        outputs.push((
-            probability, 
-            Distribution {
-                flower_type,
-                flower_color: *color,
-                inner: filtered,
-            }
-        ))
     }
+    split_by_color(context, &chances, |color, chances| {
+        Distribution::from_chances(context.flower_type, color, chances)
+    })
+}
 
-    outputs
+// the genotype weights of a child of a and b, before its color is seen,
+// and what they are out of. this is a weighted sum of table rows.
+fn offspring(a: &Distribution, b: &Distribution) -> ([u128; 81], u128) {
+    let mut children = [0u128; 81];
+    for (parent1, n1) in a.numerators() {
+        for (parent2, n2) in b.numerators() {
+            let pair = parent1 * 81 + parent2;
+            let row = &OFFSPRING[OFFSPRING_STARTS[pair] as usize..OFFSPRING_STARTS[pair + 1] as usize];
+            let scale = n1 as u128 * n2 as u128;
+            for &(child, numerator) in row {
+                children[child as usize] += scale * numerator as u128;
+            }
+        }
+    }
+    (children, a.total() as u128 * b.total() as u128 * 256)
 }
 
-pub fn as_one_row<T: Copy + Clone + Default>(mat: CsMat<T>) -> CsVec<T> {
-    let mat = mat.to_csr();
-    let (row, col) = mat.shape();
-    let (indptr, mut indices, data) = mat.into_raw_storage();
-    let mut prev_ind = 0;
-    for (row, ind) in indptr.iter().enumerate() {
-        while prev_ind < *ind {
-            indices[prev_ind] += (row - 1) * col;
-            prev_ind += 1;
+// splits the chances of each genotype up by the color it shows, making each
+// color's distribution from its genotypes and their chances.
+fn split_by_color<F>(context: &BreedContext, chances: &[f64; 81], make: F) -> Vec<(f32, Distribution)>
+where
+    F: Fn(FlowerColor, &[(usize, f64)]) -> Distribution,
+{
+    let mut by_color = vec![Vec::new(); context.colors.len()];
+    for (index, &chance) in chances.iter().enumerate() {
+        if chance > 0f64 {
+            by_color[context.color_of[index]].push((index, chance));
         }
-        prev_ind = *ind;
     }
 
-    CsVec::new(row * col, indices, data)
-}
\ No newline at end of file
+    context.colors.iter().zip(by_color).filter(|(_, genotypes)| !genotypes.is_empty()).map(|(&color, genotypes)| {
+        let probability: f64 = genotypes.iter().map(|(_, p)| p).sum();
+        (probability as f32, make(color, &genotypes))
+    }).collect()
+}
@@ -0,0 +1,53 @@
+use std::fmt::Write;
+
+use crate::{arena::{Arena, PathId}, path::{Path, StepSource}};
+
+// renders a plan as a graphviz digraph, drawing each distinct step once.
+pub fn to_dot(arena: &Arena, path: PathId) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph plan {{").unwrap();
+    writeln!(out, "    rankdir=LR;").unwrap();
+    writeln!(out, "    node [fontname=\"monospace\"];").unwrap();
+
+    let steps = Path::unique_steps(arena, path);
+    for (id, &(step, ref source)) in steps.iter().enumerate() {
+        let (step, target) = (&arena[step], arena.target(step));
+        let genotypes: Vec<_> = target.genotypes().map(|(g, _)| format!("{:?}", g)).collect();
+        match source {
+            StepSource::Owned => {
+                let label = format!("{}\\n{:?}\\n{}", escape(step.label().unwrap()), target.flower_color, genotypes.join("\\n"));
+                writeln!(out, "    n{} [label=\"{}\", shape=box, style=\"rounded,filled\", fillcolor=\"lightgrey\"];", id, label).unwrap();
+            }
+            StepSource::Breed(_, _) => {
+                let label = format!("{:?}\\n{}\\nt={}", target.flower_color, genotypes.join("\\n"), step.expected_time);
+                writeln!(out, "    n{} [label=\"{}\", shape=ellipse];", id, label).unwrap();
+            }
+            StepSource::Clone(_) => {
+                let label = format!("clone\\n{:?}\\nt={}", target.flower_color, step.expected_time);
+                writeln!(out, "    n{} [label=\"{}\", shape=ellipse, style=dashed];", id, label).unwrap();
+            }
+            StepSource::Purify(_) => {
+                let label = format!("purify\\n{:?}\\n{}\\nt={}", target.flower_color, genotypes.join("\\n"), step.expected_time);
+                writeln!(out, "    n{} [label=\"{}\", shape=doublecircle];", id, label).unwrap();
+            }
+        }
+    }
+    for (id, (_, source)) in steps.iter().enumerate() {
+        match source {
+            StepSource::Owned => {}
+            StepSource::Breed(left, right) => {
+                writeln!(out, "    n{} -> n{};", left, id).unwrap();
+                writeln!(out, "    n{} -> n{};", right, id).unwrap();
+            }
+            StepSource::Clone(original) => writeln!(out, "    n{} -> n{} [style=dashed];", original, id).unwrap(),
+            StepSource::Purify(original) => writeln!(out, "    n{} -> n{} [style=bold];", original, id).unwrap(),
+        }
+    }
+
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,7 +1,7 @@
 use crate::{distribution::dist_index_from_genotype, genotype::Genotype};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum FlowerType {
     Rose,
     Cosmo,
@@ -13,7 +13,7 @@ pub enum FlowerType {
     Windflower,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum FlowerColor {
     White,
     Yellow,
@@ -24,8 +24,15 @@ pub enum FlowerColor {
     Black,
     Blue,
     Green,
+    // only roses, and only from black roses watered with the golden
+    // watering can. a gold rose has the genotype of a black one.
+    Gold,
 }
 
+// a pair of black roses watered with the golden watering can turns each
+// black child gold with this chance.
+pub const GOLD_CHANCE: f32 = 0.5;
+
 pub const ROSES_LIST: [FlowerColor; 81] = {
     [
         FlowerColor::White,
@@ -336,16 +343,71 @@ pub const WINDFLOWERS_LIST: [FlowerColor; 27] = {
     ]
 };
 
-pub fn get_color(flower_type: &FlowerType, genotype: Genotype) -> FlowerColor {
-    let index = dist_index_from_genotype(genotype);
+pub fn species_name(flower_type: &FlowerType) -> &'static str {
     match flower_type {
-        FlowerType::Rose => ROSES_LIST[index],
-        FlowerType::Cosmo => COSMOS_LIST[index],
-        FlowerType::Lily => LILIES_LIST[index],
-        FlowerType::Pansy => PANSIES_LIST[index],
-        FlowerType::Tulip => TULIPS_LIST[index],
-        FlowerType::Hyacinth => HYACINTHS_LIST[index],
-        FlowerType::Mum => MUMS_LIST[index],
-        FlowerType::Windflower => WINDFLOWERS_LIST[index],
+        FlowerType::Rose => "rose",
+        FlowerType::Cosmo => "cosmos",
+        FlowerType::Lily => "lily",
+        FlowerType::Pansy => "pansy",
+        FlowerType::Tulip => "tulip",
+        FlowerType::Hyacinth => "hyacinth",
+        FlowerType::Mum => "mum",
+        FlowerType::Windflower => "windflower",
     }
-}
\ No newline at end of file
+}
+
+pub fn color_list(flower_type: &FlowerType) -> &'static [FlowerColor] {
+    match flower_type {
+        FlowerType::Rose => &ROSES_LIST,
+        FlowerType::Cosmo => &COSMOS_LIST,
+        FlowerType::Lily => &LILIES_LIST,
+        FlowerType::Pansy => &PANSIES_LIST,
+        FlowerType::Tulip => &TULIPS_LIST,
+        FlowerType::Hyacinth => &HYACINTHS_LIST,
+        FlowerType::Mum => &MUMS_LIST,
+        FlowerType::Windflower => &WINDFLOWERS_LIST,
+    }
+}
+
+// the genotypes of the seeds sold for each species, by seed name.
+// three gene species leave the first gene recessive.
+pub fn seeds(flower_type: &FlowerType) -> [(&'static str, Genotype); 3] {
+    let [red, other, white] = match flower_type {
+        FlowerType::Rose => [0b11000001, 0b00110000, 0b00000100],
+        FlowerType::Cosmo => [0b00110001, 0b00001101, 0b00000001],
+        FlowerType::Lily => [0b00110001, 0b00001100, 0b00000001],
+        FlowerType::Pansy => [0b00110000, 0b00001100, 0b00000001],
+        FlowerType::Tulip => [0b00110001, 0b00001100, 0b00000001],
+        FlowerType::Hyacinth => [0b00110001, 0b00001101, 0b00000001],
+        FlowerType::Mum => [0b00110011, 0b00001111, 0b00000001],
+        FlowerType::Windflower => [0b00110011, 0b00001111, 0b00000001],
+    };
+    let other_name = match flower_type {
+        FlowerType::Windflower => "orange",
+        _ => "yellow",
+    };
+    [
+        ("red", Genotype::new(red)),
+        (other_name, Genotype::new(other)),
+        ("white", Genotype::new(white)),
+    ]
+}
+
+// whether golden watering can turn children of this flower gold.
+pub fn can_turn_gold(flower_type: &FlowerType, genotype: Genotype) -> bool {
+    *flower_type == FlowerType::Rose && get_color(flower_type, genotype) == FlowerColor::Black
+}
+
+// the color the genotypes of a flower of this color show, which for gold
+// roses is black.
+pub fn genotype_color(flower_type: &FlowerType, flower_color: FlowerColor) -> FlowerColor {
+    match (flower_type, flower_color) {
+        (FlowerType::Rose, FlowerColor::Gold) => FlowerColor::Black,
+        _ => flower_color,
+    }
+}
+
+pub fn get_color(flower_type: &FlowerType, genotype: Genotype) -> FlowerColor {
+    let index = dist_index_from_genotype(genotype);
+    color_list(flower_type)[index]
+}
@@ -0,0 +1,123 @@
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use serde::Serialize;
+
+use crate::{flowers::{FlowerColor, FlowerType, get_color}, genotype::Genotype};
+
+// a rectangle of tiles, each empty or holding a flower of one species.
+pub struct Garden {
+    pub flower_type: FlowerType,
+    pub width: usize,
+    pub height: usize,
+    tiles: Vec<Option<Genotype>>,
+}
+
+// a flower that appeared in the garden.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Birth {
+    pub day: usize,
+    pub position: (usize, usize),
+    pub parents: [(usize, usize); 2],
+    pub color: FlowerColor,
+    pub genotype: String,
+}
+
+impl Garden {
+    pub fn new(flower_type: FlowerType, width: usize, height: usize) -> Self {
+        Self {
+            flower_type,
+            width,
+            height,
+            tiles: vec![None; width * height],
+        }
+    }
+
+    pub fn get(&self, tile: (usize, usize)) -> Option<Genotype> {
+        self.tiles[self.index(tile)]
+    }
+
+    pub fn set(&mut self, tile: (usize, usize), genotype: Option<Genotype>) {
+        let index = self.index(tile);
+        self.tiles[index] = genotype;
+    }
+
+    // the up to eight tiles touching a tile.
+    pub fn neighbors(&self, (x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let (width, height) = (self.width as isize, self.height as isize);
+        (-1isize..=1).flat_map(move |dy| (-1isize..=1).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| dx != 0 || dy != 0)
+            .map(move |(dx, dy)| (x as isize + dx, y as isize + dy))
+            .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width && ny < height)
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+    }
+
+    // runs the garden for some days and returns every flower that appeared.
+    //
+    // each day the flowers are visited in a random order. a flower that
+    // hasn't bred yet that day breeds with breed_rate chance, picking a random
+    // neighbor that also hasn't bred. the child goes on a random empty tile
+    // next to the flower that started it, and if there isn't one nothing
+    // grows. new flowers don't breed until the next day.
+    pub fn simulate(&mut self, days: usize, breed_rate: f32, seed: u64) -> Vec<Birth> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut births = Vec::new();
+
+        for day in 1..=days {
+            let mut bred = vec![false; self.tiles.len()];
+            let mut order: Vec<_> = (0..self.height)
+                .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+                .filter(|&tile| self.get(tile).is_some())
+                .collect();
+            order.shuffle(&mut rng);
+
+            for tile in order {
+                if bred[self.index(tile)] || !rng.gen_bool(breed_rate as f64) {
+                    continue;
+                }
+                let partners: Vec<_> = self.neighbors(tile)
+                    .filter(|&n| self.get(n).is_some() && !bred[self.index(n)])
+                    .collect();
+                let partner = match partners.choose(&mut rng) {
+                    Some(&partner) => partner,
+                    None => continue,
+                };
+                bred[self.index(tile)] = true;
+                bred[self.index(partner)] = true;
+
+                let empty: Vec<_> = self.neighbors(tile).filter(|&n| self.get(n).is_none()).collect();
+                let position = match empty.choose(&mut rng) {
+                    Some(&position) => position,
+                    None => continue,
+                };
+
+                let genotype = self.cross(self.get(tile).unwrap(), self.get(partner).unwrap(), &mut rng);
+                self.set(position, Some(genotype));
+                bred[self.index(position)] = true;
+                births.push(Birth {
+                    day,
+                    position,
+                    parents: [tile, partner],
+                    color: get_color(&self.flower_type, genotype),
+                    genotype: format!("{:?}", genotype),
+                });
+            }
+        }
+
+        births
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        y * self.width + x
+    }
+
+    fn cross<R: Rng>(&self, left: Genotype, right: Genotype, rng: &mut R) -> Genotype {
+        let mut roll = rng.gen_range(0..256);
+        for (numerator, genotype) in left.breed(&right) {
+            if roll < numerator {
+                return genotype;
+            }
+            roll -= numerator;
+        }
+        unreachable!()
+    }
+}
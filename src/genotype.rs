@@ -1,4 +1,4 @@
-use std::{rc::Rc, fmt};
+use std::{fmt, str::FromStr};
 
 const U1_LOOKUP: [u8; 2] = [
     0b0,
@@ -37,6 +37,29 @@ impl fmt::Debug for Genotype {
     }
 }
 
+// parses the same format the debug output uses, eg "RRyyWwSs".
+// three gene species write the first gene as "rr".
+impl FromStr for Genotype {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let dominant = "RRYYwwSS";
+        let recessive = "rryyWWss";
+        if s.chars().count() != 8 {
+            return Err(format!("genotype \"{}\" should have 8 letters, like \"{}\"", s, recessive));
+        }
+        let mut inner = 0u8;
+        for (i, (c, (a, b))) in s.chars().zip(recessive.chars().zip(dominant.chars())).enumerate() {
+            if c == b {
+                inner |= 1 << (7 - i);
+            } else if c != a {
+                return Err(format!("genotype \"{}\" has '{}' where '{}' or '{}' was expected", s, c, a, b));
+            }
+        }
+        Ok(Genotype::new(inner))
+    }
+}
+
 impl fmt::Binary for Genotype {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let val = self.0;
@@ -47,7 +70,7 @@ impl fmt::Binary for Genotype {
 
 impl Genotype {
     pub fn new(mut inner: u8) -> Self {
-        for i in vec![0, 2, 4, 6] {
+        for i in [0, 2, 4, 6] {
             if inner & (0b11 << i) == (0b10 << i) {
                 inner ^= 0b11 << i;
             }
@@ -96,11 +119,11 @@ impl Genotype {
 
     pub fn from_base_3(mut from: u8) -> Genotype {
         let mut a4 = from % 3;
-        from = from / 3;
+        from /= 3;
         let mut a3 = from % 3;
-        from = from / 3;
+        from /= 3;
         let mut a2 = from % 3;
-        from = from / 3;
+        from /= 3;
         let mut a1 = from % 3;
 
         a1 = match a1 {
@@ -0,0 +1,464 @@
+use std::{collections::{BTreeMap, HashSet}, fmt, path::Path as FilePath};
+
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::{arena::{Arena, PathId}, constraints::Constraints, garden::Garden, cost::{CostModel, Objective}, distribution::{Distribution, dist_index_from_genotype}, flowers::{FlowerColor, FlowerType, color_list, genotype_color, get_color, seeds}, genotype::Genotype, path::Path, planner::Target};
+
+// the on disk description of a planning run, in json or toml.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RunFile {
+    species: FlowerType,
+    flowers: Vec<FlowerEntry>,
+    target: TargetEntry,
+    #[serde(default)]
+    cost_model: CostModel,
+    #[serde(default)]
+    constraints: Constraints,
+}
+
+// a garden to simulate, drawn as rows of characters. each character other
+// than '.' or ' ' is a flower described in the legend.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct GardenFile {
+    species: FlowerType,
+    layout: Vec<String>,
+    legend: BTreeMap<char, FlowerEntry>,
+    days: usize,
+    #[serde(default = "default_breed_rate")]
+    breed_rate: f32,
+}
+
+fn default_breed_rate() -> f32 {
+    CostModel::default().breed_rate
+}
+
+// a flower planted next to several partners at once.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct MixFile {
+    species: FlowerType,
+    flower: FlowerEntry,
+    partners: Vec<PartnerEntry>,
+}
+
+// a flower of uncertain genotype to purify into one genotype, with flowers
+// of known genotype to test it against. the testers default to the seeds.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PurifyFile {
+    species: FlowerType,
+    flower: FlowerEntry,
+    genotype: String,
+    testers: Option<Vec<FlowerEntry>>,
+    #[serde(default)]
+    cost_model: CostModel,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PartnerEntry {
+    #[serde(default = "default_weight")]
+    weight: f32,
+    flower: FlowerEntry,
+}
+
+fn default_weight() -> f32 {
+    1f32
+}
+
+// an owned flower. exactly one of genotype, seed or color must be given;
+// a color alone means the genotype is unknown. seeds can always be bought
+// again, other flowers are taken to be the only copy unless copies says
+// otherwise.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FlowerEntry {
+    label: Option<String>,
+    genotype: Option<String>,
+    seed: Option<String>,
+    color: Option<FlowerColor>,
+    copies: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TargetEntry {
+    color: FlowerColor,
+    genotypes: Option<Vec<String>>,
+}
+
+pub struct Run {
+    pub flower_type: FlowerType,
+    // every path of the run, starting with the sources.
+    pub arena: Arena,
+    pub sources: Vec<PathId>,
+    pub target: Target,
+    pub cost_model: CostModel,
+    pub constraints: Constraints,
+}
+
+pub struct GardenRun {
+    pub garden: Garden,
+    pub days: usize,
+    pub breed_rate: f32,
+}
+
+pub struct MixRun {
+    pub flower: Distribution,
+    pub partners: Vec<(f32, Distribution)>,
+}
+
+pub struct PurifyRun {
+    pub flower: Distribution,
+    // whether there is more than one of the flower.
+    pub copy: bool,
+    pub genotype: Genotype,
+    pub testers: Vec<Genotype>,
+    pub cost_model: CostModel,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(String, std::io::Error),
+    Parse(String, String),
+    Invalid(String, String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(file, e) => write!(f, "could not read {}: {}", file, e),
+            LoadError::Parse(file, e) => write!(f, "{} is not a valid input file: {}", file, e),
+            LoadError::Invalid(location, e) => write!(f, "{}: {}", location, e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl Run {
+    pub fn load(file: &str) -> Result<Self, LoadError> {
+        Self::from_run_file(read_file(file)?)
+    }
+
+    fn from_run_file(run_file: RunFile) -> Result<Self, LoadError> {
+        let flower_type = run_file.species;
+
+        let mut arena = Arena::default();
+        let mut sources = Vec::new();
+        let mut labels = HashSet::new();
+        for (i, entry) in run_file.flowers.into_iter().enumerate() {
+            let location = match &entry.label {
+                Some(label) => format!("flowers[{}] (\"{}\")", i, label),
+                None => format!("flowers[{}]", i),
+            };
+            let invalid = |e: String| LoadError::Invalid(location.clone(), e);
+
+            let (label, distribution) = entry.resolve(&flower_type).map_err(invalid)?;
+            let label = entry.label.unwrap_or(label);
+            if !labels.insert(label.clone()) {
+                return Err(invalid(format!("the label \"{}\" is used more than once", label)));
+            }
+            let single = match entry.copies {
+                Some(0) => return Err(invalid("copies must be at least 1".to_string())),
+                Some(copies) => copies == 1,
+                None => entry.seed.is_none(),
+            };
+            let target = arena.intern(distribution);
+            if single {
+                sources.push(arena.add(Path::new_single(label, target)));
+            } else {
+                sources.push(arena.add(Path::new(label, target)));
+            }
+        }
+
+        let color = run_file.target.color;
+        if Distribution::from_color(flower_type, color).is_none() {
+            return Err(LoadError::Invalid("target.color".to_string(), format!("there is no {:?} {:?}", color, flower_type)));
+        }
+        let genotypes = match run_file.target.genotypes {
+            Some(genotypes) => {
+                let parsed = genotypes.iter().enumerate().map(|(i, g)| {
+                    let invalid = |e: String| LoadError::Invalid(format!("target.genotypes[{}]", i), e);
+                    let genotype = parse_genotype(&flower_type, g).map_err(invalid)?;
+                    let shown = get_color(&flower_type, genotype);
+                    if shown != genotype_color(&flower_type, color) {
+                        return Err(invalid(format!("{} is {:?}, not {:?}", g, shown, color)));
+                    }
+                    Ok(genotype)
+                });
+                Some(parsed.collect::<Result<Vec<_>, _>>()?)
+            }
+            None => None,
+        };
+        let target = Target {
+            color,
+            genotypes,
+        };
+
+        if let Some(allowed) = &run_file.constraints.allowed_sources {
+            for (i, label) in allowed.iter().enumerate() {
+                if !labels.contains(label) {
+                    let location = format!("constraints.allowedSources[{}]", i);
+                    return Err(LoadError::Invalid(location, format!("no flower is labelled \"{}\"", label)));
+                }
+            }
+        }
+
+        check_cost_model(&run_file.cost_model)?;
+
+        Ok(Self {
+            flower_type,
+            arena,
+            sources,
+            target,
+            cost_model: run_file.cost_model,
+            constraints: run_file.constraints,
+        })
+    }
+}
+
+impl GardenRun {
+    pub fn load(file: &str) -> Result<Self, LoadError> {
+        Self::from_garden_file(read_file(file)?)
+    }
+
+    fn from_garden_file(garden_file: GardenFile) -> Result<Self, LoadError> {
+        let flower_type = garden_file.species;
+        let height = garden_file.layout.len();
+        let width = garden_file.layout.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let mut garden = Garden::new(flower_type, width, height);
+
+        let mut legend = BTreeMap::new();
+        for (key, entry) in &garden_file.legend {
+            let location = format!("legend.{}", key);
+            let (_, distribution) = entry.resolve(&flower_type).map_err(|e| LoadError::Invalid(location.clone(), e))?;
+            let mut genotypes = distribution.genotypes();
+            match (genotypes.next(), genotypes.next()) {
+                (Some((genotype, _)), None) => legend.insert(*key, genotype),
+                _ => {
+                    let message = "a garden needs exact genotypes, so give a genotype or seed".to_string();
+                    return Err(LoadError::Invalid(location, message));
+                }
+            };
+        }
+
+        for (y, row) in garden_file.layout.iter().enumerate() {
+            for (x, key) in row.chars().enumerate() {
+                if key == '.' || key == ' ' {
+                    continue;
+                }
+                match legend.get(&key) {
+                    Some(genotype) => garden.set((x, y), Some(*genotype)),
+                    None => {
+                        let location = format!("layout[{}], column {}", y, x + 1);
+                        return Err(LoadError::Invalid(location, format!("'{}' isn't in the legend", key)));
+                    }
+                }
+            }
+        }
+
+        if !(garden_file.breed_rate > 0f32 && garden_file.breed_rate <= 1f32) {
+            let location = "breedRate".to_string();
+            return Err(LoadError::Invalid(location, format!("{} is not a chance between 0 and 1", garden_file.breed_rate)));
+        }
+
+        Ok(Self {
+            garden,
+            days: garden_file.days,
+            breed_rate: garden_file.breed_rate,
+        })
+    }
+}
+
+impl MixRun {
+    pub fn load(file: &str) -> Result<Self, LoadError> {
+        Self::from_mix_file(read_file(file)?)
+    }
+
+    fn from_mix_file(mix_file: MixFile) -> Result<Self, LoadError> {
+        let flower_type = mix_file.species;
+        let (_, flower) = mix_file.flower.resolve(&flower_type)
+            .map_err(|e| LoadError::Invalid("flower".to_string(), e))?;
+
+        let mut partners = Vec::new();
+        for (i, partner) in mix_file.partners.iter().enumerate() {
+            let (_, distribution) = partner.flower.resolve(&flower_type)
+                .map_err(|e| LoadError::Invalid(format!("partners[{}].flower", i), e))?;
+            if !(partner.weight > 0f32 && partner.weight.is_finite()) {
+                let location = format!("partners[{}].weight", i);
+                return Err(LoadError::Invalid(location, format!("{} is not a positive weight", partner.weight)));
+            }
+            partners.push((partner.weight, distribution));
+        }
+        if partners.is_empty() {
+            return Err(LoadError::Invalid("partners".to_string(), "at least one partner is needed".to_string()));
+        }
+
+        Ok(Self { flower, partners })
+    }
+}
+
+impl PurifyRun {
+    pub fn load(file: &str) -> Result<Self, LoadError> {
+        Self::from_purify_file(read_file(file)?)
+    }
+
+    fn from_purify_file(purify_file: PurifyFile) -> Result<Self, LoadError> {
+        let flower_type = purify_file.species;
+        let (_, flower) = purify_file.flower.resolve(&flower_type)
+            .map_err(|e| LoadError::Invalid("flower".to_string(), e))?;
+        let genotype = parse_genotype(&flower_type, &purify_file.genotype)
+            .map_err(|e| LoadError::Invalid("genotype".to_string(), e))?;
+        check_cost_model(&purify_file.cost_model)?;
+
+        let testers = match &purify_file.testers {
+            Some(testers) => {
+                let mut genotypes = Vec::new();
+                for (i, tester) in testers.iter().enumerate() {
+                    let location = format!("testers[{}]", i);
+                    let (_, distribution) = tester.resolve(&flower_type).map_err(|e| LoadError::Invalid(location.clone(), e))?;
+                    let mut known = distribution.genotypes();
+                    match (known.next(), known.next()) {
+                        (Some((genotype, _)), None) => genotypes.push(genotype),
+                        _ => {
+                            let message = "a tester needs an exact genotype, so give a genotype or seed".to_string();
+                            return Err(LoadError::Invalid(location, message));
+                        }
+                    }
+                }
+                genotypes
+            }
+            None => seeds(&flower_type).iter().map(|(_, genotype)| *genotype).collect(),
+        };
+
+        Ok(Self {
+            flower,
+            copy: purify_file.flower.seed.is_some() || purify_file.flower.copies.is_some_and(|copies| copies > 1),
+            genotype,
+            testers,
+            cost_model: purify_file.cost_model,
+        })
+    }
+}
+
+impl FlowerEntry {
+    // the flower's default label and what its genotype could be.
+    fn resolve(&self, flower_type: &FlowerType) -> Result<(String, Distribution), String> {
+        match (&self.genotype, &self.seed, self.color) {
+            (Some(genotype), None, None) => {
+                let genotype = parse_genotype(flower_type, genotype)?;
+                Ok((format!("{:?}", genotype), Distribution::new(*flower_type, genotype)))
+            }
+            (None, Some(seed), None) => {
+                let known = seeds(flower_type);
+                match known.iter().find(|(name, _)| name == seed) {
+                    Some((_, genotype)) => Ok((format!("{} seed", seed), Distribution::new(*flower_type, *genotype))),
+                    None => {
+                        let names: Vec<_> = known.iter().map(|(name, _)| *name).collect();
+                        Err(format!("unknown {:?} seed \"{}\", expected one of {}", flower_type, seed, names.join(", ")))
+                    }
+                }
+            }
+            (None, None, Some(color)) => match Distribution::from_color(*flower_type, color) {
+                Some(distribution) => Ok((format!("{:?} {:?}", color, flower_type).to_lowercase(), distribution)),
+                None => Err(format!("there is no {:?} {:?}", color, flower_type)),
+            },
+            _ => Err("exactly one of genotype, seed or color must be given".to_string()),
+        }
+    }
+}
+
+fn check_cost_model(cost_model: &CostModel) -> Result<(), LoadError> {
+    let breed_rate = cost_model.breed_rate;
+    if !(breed_rate > 0f32 && breed_rate <= 1f32) {
+        let location = "costModel.breedRate".to_string();
+        return Err(LoadError::Invalid(location, format!("{} is not a chance between 0 and 1", breed_rate)));
+    }
+
+    let clone_rate = cost_model.clone_rate;
+    if !(clone_rate > 0f32 && clone_rate <= 1f32) {
+        let location = "costModel.cloneRate".to_string();
+        return Err(LoadError::Invalid(location, format!("{} is not a chance between 0 and 1", clone_rate)));
+    }
+
+    let confidence = cost_model.purify_confidence;
+    if !(confidence > 0f32 && confidence <= 1f32) {
+        let location = "costModel.purifyConfidence".to_string();
+        return Err(LoadError::Invalid(location, format!("{} is not a chance between 0 and 1", confidence)));
+    }
+
+    if let Objective::Percentile(fraction) = cost_model.objective {
+        if !(fraction > 0f32 && fraction < 1f32) {
+            let location = "costModel.objective.percentile".to_string();
+            return Err(LoadError::Invalid(location, format!("{} is not a chance between 0 and 1", fraction)));
+        }
+    }
+
+    Ok(())
+}
+
+// reads json, or toml when the file name ends in .toml.
+pub fn read_file<T: DeserializeOwned>(file: &str) -> Result<T, LoadError> {
+    let contents = std::fs::read_to_string(file).map_err(|e| LoadError::Io(file.to_string(), e))?;
+    let is_toml = FilePath::new(file).extension().is_some_and(|e| e == "toml");
+    if is_toml {
+        toml::from_str(&contents).map_err(|e| LoadError::Parse(file.to_string(), e.to_string()))
+    } else {
+        serde_json::from_str(&contents).map_err(|e| LoadError::Parse(file.to_string(), e.to_string()))
+    }
+}
+
+pub fn parse_genotype(flower_type: &FlowerType, genotype: &str) -> Result<Genotype, String> {
+    let parsed: Genotype = genotype.parse()?;
+    if dist_index_from_genotype(parsed) >= color_list(flower_type).len() {
+        return Err(format!("{:?} has three genes, so genotype \"{}\" must start with \"rr\"", flower_type, genotype));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+impl Run {
+    pub fn from_json(json: &str) -> Self {
+        Self::from_run_file(serde_json::from_str(json).unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(json: &str) -> Result<Run, LoadError> {
+        Run::from_run_file(serde_json::from_str(json).unwrap())
+    }
+
+    fn location(json: &str) -> String {
+        match load(json) {
+            Err(LoadError::Invalid(location, _)) => location,
+            Err(e) => panic!("{}", e),
+            Ok(_) => panic!("loaded"),
+        }
+    }
+
+    #[test]
+    fn refuses_target_genotypes_of_another_color() {
+        let json = r#"{
+            "species": "Rose",
+            "flowers": [{ "seed": "red" }],
+            "target": { "color": "Purple", "genotypes": ["rryywwss", "RRYYwwss"] }
+        }"#;
+        assert_eq!(location(json), "target.genotypes[1]");
+    }
+
+    #[test]
+    fn refuses_target_colors_the_species_lacks() {
+        let blue_lily = r#"{ "species": "Lily", "flowers": [{ "seed": "red" }], "target": { "color": "Blue" } }"#;
+        assert_eq!(location(blue_lily), "target.color");
+        let gold_tulip = r#"{ "species": "Tulip", "flowers": [{ "seed": "red" }], "target": { "color": "Gold" } }"#;
+        assert_eq!(location(gold_tulip), "target.color");
+        let gold_rose = r#"{ "species": "Rose", "flowers": [{ "seed": "red" }], "target": { "color": "Gold", "genotypes": ["RRyyWWss"] } }"#;
+        assert!(load(gold_rose).is_ok());
+    }
+}
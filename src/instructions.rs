@@ -0,0 +1,167 @@
+use std::fmt::Write;
+
+use crate::{arena::{Arena, PathId}, distribution::Distribution, flowers::{FlowerColor, FlowerType, species_name}, path::{Path, Step, StepSource}};
+
+// continuation lines line up under the text of a numbered step, which
+// also keeps them inside the list item in markdown.
+const INDENT: &str = "   ";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Text,
+    Markdown,
+}
+
+// numbered steps a person can follow in game: what to plant next to what,
+// which child to keep, which flowers to leave alone so they spread a copy,
+// and whether each is the end of the plan or needed later.
+pub fn render(arena: &Arena, path: PathId, style: Style) -> String {
+    let steps = Path::unique_steps(arena, path);
+    let flower_type = arena.target(path).flower_type;
+
+    let numbers = step_numbers(&steps);
+
+    let mut out = String::new();
+    writeln!(out, "{}", emphasis("Start with:", style)).unwrap();
+    writeln!(out).unwrap();
+    for &(step, _) in steps.iter().filter(|(_, source)| *source == StepSource::Owned) {
+        let target = arena.target(step);
+        writeln!(out, "- {} ({:?}, {})", arena[step].label().unwrap(), target.flower_color, genotype_list(target, style)).unwrap();
+    }
+
+    for (id, &(step, ref source)) in steps.iter().enumerate() {
+        let (step, target) = (&arena[step], arena.target(step));
+        let name = |i: usize| emphasis(&describe(arena, &steps, &numbers, i, flower_type), style);
+        let parent_time = match *source {
+            StepSource::Owned => continue,
+            StepSource::Breed(left, right) => {
+                writeln!(out).unwrap();
+                let water = match target.flower_color {
+                    FlowerColor::Gold => ", then water them with the golden watering can",
+                    _ => "",
+                };
+                if left == right && matches!(steps[left].1, StepSource::Clone(_)) {
+                    writeln!(out, "{}. Plant {} next to its copy{}.", numbers[id].unwrap(), name(left), water).unwrap();
+                } else if left == right {
+                    writeln!(out, "{}. Plant {} next to another {}{}.", numbers[id].unwrap(), name(left), name(left), water).unwrap();
+                } else {
+                    writeln!(out, "{}. Plant {} next to {}{}.", numbers[id].unwrap(), name(left), name(right), water).unwrap();
+                }
+
+                let color = emphasis(&format!("{:?} {}", target.flower_color, species_name(&flower_type)).to_lowercase(), style);
+                if target.genotype_count() == 1 {
+                    writeln!(out, "{}Keep the first {}; it will be {}.", INDENT, color, genotype_list(target, style)).unwrap();
+                } else {
+                    writeln!(out, "{}Keep the first {}; it could be {}.", INDENT, color, genotype_list(target, style)).unwrap();
+                }
+                arena[steps[left].0].expected_time.max(arena[steps[right].0].expected_time)
+            }
+            StepSource::Clone(original) => {
+                writeln!(out).unwrap();
+                writeln!(out, "{}. Leave {} with nothing next to it until it spreads a copy.", numbers[id].unwrap(), name(original)).unwrap();
+                arena[steps[original].0].expected_time
+            }
+            StepSource::Purify(original) => {
+                let (_, purification) = step.purified_from().unwrap();
+                writeln!(out).unwrap();
+                writeln!(
+                    out,
+                    "{}. Purify {} into {}, crossing it with a copy of itself or with flowers of known genotype",
+                    numbers[id].unwrap(),
+                    name(original),
+                    code(&format!("{:?}", purification.genotype), style),
+                ).unwrap();
+                writeln!(out, "{}and keeping or throwing away each child by its color.", INDENT).unwrap();
+                writeln!(out, "{}The json output has the full table of what to cross and which children to keep.", INDENT).unwrap();
+                arena[steps[original].0].expected_time
+            }
+        };
+
+        writeln!(
+            out,
+            "{}Expect this to take {}, finishing around day {}.",
+            INDENT,
+            days(step.expected_time - parent_time),
+            round(step.expected_time),
+        ).unwrap();
+
+        let used_by: Vec<_> = steps
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, source))| match *source {
+                StepSource::Owned => false,
+                StepSource::Breed(left, right) => left == id || right == id,
+                StepSource::Clone(original) | StepSource::Purify(original) => original == id,
+            })
+            .map(|(i, _)| format!("{}", numbers[i].unwrap()))
+            .collect();
+        if used_by.is_empty() {
+            writeln!(out, "{}This is the final flower.", INDENT).unwrap();
+        } else if used_by.len() == 1 {
+            writeln!(out, "{}Keep it around for step {}.", INDENT, used_by[0]).unwrap();
+        } else {
+            writeln!(out, "{}Keep it around for steps {}.", INDENT, used_by.join(", ")).unwrap();
+        }
+    }
+
+    out
+}
+
+// bred and cloned steps are numbered in order, sources are referred to by
+// label.
+pub fn step_numbers(steps: &[Step]) -> Vec<Option<usize>> {
+    let mut numbers = vec![None; steps.len()];
+    let mut next_number = 1;
+    for (id, (_, source)) in steps.iter().enumerate() {
+        if *source != StepSource::Owned {
+            numbers[id] = Some(next_number);
+            next_number += 1;
+        }
+    }
+    numbers
+}
+
+pub fn describe(arena: &Arena, steps: &[Step], numbers: &[Option<usize>], id: usize, flower_type: FlowerType) -> String {
+    let (step, _) = steps[id];
+    match (arena[step].label(), numbers[id]) {
+        (Some(label), _) => label.to_string(),
+        (None, Some(number)) => format!("the {:?} {} from step {}", arena.target(step).flower_color, species_name(&flower_type), number).to_lowercase(),
+        (None, None) => unreachable!(),
+    }
+}
+
+fn genotype_list(distribution: &Distribution, style: Style) -> String {
+    or_list(distribution.genotypes().map(|(g, _)| code(&format!("{:?}", g), style)).collect())
+}
+
+fn or_list(items: Vec<String>) -> String {
+    match items.len() {
+        0 | 1 => items.join(""),
+        n => format!("{} or {}", items[..n - 1].join(", "), items[n - 1]),
+    }
+}
+
+fn round(days: f32) -> String {
+    format!("{:.1}", days).trim_end_matches(".0").to_string()
+}
+
+fn days(days: f32) -> String {
+    match round(days).as_str() {
+        "1" => "1 day".to_string(),
+        rounded => format!("{} days", rounded),
+    }
+}
+
+fn emphasis(text: &str, style: Style) -> String {
+    match style {
+        Style::Text => text.to_string(),
+        Style::Markdown => format!("**{}**", text),
+    }
+}
+
+fn code(text: &str, style: Style) -> String {
+    match style {
+        Style::Text => text.to_string(),
+        Style::Markdown => format!("`{}`", text),
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::{arena::{Arena, PathId}, instructions::{describe, step_numbers}, path::{Path, StepSource}};
+
+// every cross gets a block of its own with the pair in the middle, eg
+//
+//     ....
+//     .ab.
+//     ....
+//
+// so the pair has free tiles all around to spawn children on. a child on the
+// edge of a block would touch a child on the edge of the block beside it, so
+// blocks are kept a tile apart and nothing of one block ever touches
+// another. a flower being cloned gets a block to itself in the same way.
+const BLOCK_LONG: usize = 4;
+const BLOCK_SHORT: usize = 3;
+const SPACING: usize = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layout {
+    pub width: usize,
+    pub height: usize,
+    pub stations: Vec<Station>,
+}
+
+// where one cross of the plan is planted, or the one flower of a clone.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Station {
+    pub step: usize,
+    pub flowers: Vec<Planting>,
+    pub spawn_tiles: Vec<(usize, usize)>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Planting {
+    pub flower: String,
+    pub position: (usize, usize),
+}
+
+// places every cross in the plan inside a width by height rectangle, or
+// explains why they don't fit.
+pub fn layout(arena: &Arena, path: PathId, width: usize, height: usize) -> Result<Layout, String> {
+    let steps = Path::unique_steps(arena, path);
+    let numbers = step_numbers(&steps);
+    let flower_type = arena.target(path).flower_type;
+    let crosses: Vec<_> = steps
+        .iter()
+        .enumerate()
+        .filter_map(|(id, (_, source))| match *source {
+            StepSource::Owned => None,
+            StepSource::Breed(left, right) => Some((id, vec![left, right])),
+            StepSource::Clone(original) => Some((id, vec![original])),
+            StepSource::Purify(original) => Some((id, vec![original, original])),
+        })
+        .collect();
+
+    // pairs lie side by side or one above the other, whichever fits more.
+    // the last block in a row or column needs no spacing after it.
+    let fit = |tiles: usize, block: usize| (tiles + SPACING) / (block + SPACING);
+    let across = fit(width, BLOCK_LONG) * fit(height, BLOCK_SHORT);
+    let down = fit(width, BLOCK_SHORT) * fit(height, BLOCK_LONG);
+    let (block_width, block_height) = if across >= down {
+        (BLOCK_LONG, BLOCK_SHORT)
+    } else {
+        (BLOCK_SHORT, BLOCK_LONG)
+    };
+    if across.max(down) < crosses.len() {
+        return Err(format!(
+            "the plan has {} crosses and clones but only {} fit in {} by {} tiles",
+            crosses.len(),
+            across.max(down),
+            width,
+            height,
+        ));
+    }
+
+    let per_row = fit(width, block_width);
+    let stations = crosses.iter().enumerate().map(|(i, (id, parents))| {
+        let (x, y) = ((i % per_row) * (block_width + SPACING), (i / per_row) * (block_height + SPACING));
+        let first = (x + 1, y + 1);
+        let second = if block_width == BLOCK_LONG { (x + 2, y + 1) } else { (x + 1, y + 2) };
+        let flowers: Vec<_> = parents
+            .iter()
+            .zip([first, second])
+            .map(|(&parent, position)| Planting { flower: describe(arena, &steps, &numbers, parent, flower_type), position })
+            .collect();
+        let spawn_tiles = (y..y + block_height)
+            .flat_map(|ty| (x..x + block_width).map(move |tx| (tx, ty)))
+            .filter(|&tile| flowers.iter().all(|planting| planting.position != tile))
+            .collect();
+
+        Station {
+            step: numbers[*id].unwrap(),
+            flowers,
+            spawn_tiles,
+        }
+    });
+
+    Ok(Layout {
+        width,
+        height,
+        stations: stations.collect(),
+    })
+}
+
+impl Layout {
+    // the garden as rows of characters, with each cross marked by a letter,
+    // followed by a key saying what to plant at each letter.
+    pub fn to_ascii(&self) -> String {
+        let marks: Vec<char> = ('a'..='z').chain('A'..='Z').chain('0'..='9').collect();
+        let mut grid = vec![vec!['.'; self.width]; self.height];
+        for (station, &mark) in self.stations.iter().zip(marks.iter().cycle()) {
+            for planting in &station.flowers {
+                let (x, y) = planting.position;
+                grid[y][x] = mark;
+            }
+        }
+
+        let mut out = String::new();
+        for row in grid {
+            writeln!(out, "{}", row.into_iter().collect::<String>()).unwrap();
+        }
+        writeln!(out).unwrap();
+        for (station, mark) in self.stations.iter().zip(marks.iter().cycle()) {
+            match &station.flowers[..] {
+                [first, second] => writeln!(out, "{}: step {}, {} and {}", mark, station.step, first.flower, second.flower).unwrap(),
+                [only] => writeln!(out, "{}: step {}, {} on its own", mark, station.step, only.flower).unwrap(),
+                _ => unreachable!(),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{distribution::Distribution, flowers::{FlowerColor, FlowerType}, input::Run};
+
+    // red and yellow seeds crossed, white seeds crossed, then their children
+    // crossed, which is three crosses to lay out.
+    fn three_crosses() -> (Run, PathId) {
+        let mut run = Run::from_json(r#"{
+            "species": "Rose",
+            "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+            "target": { "color": "Purple" }
+        }"#);
+        let child = |run: &mut Run, left, right, color| {
+            let target = run.arena.intern(Distribution::from_color(FlowerType::Rose, color).unwrap());
+            Path::breed(&mut run.arena, left, right, &[(0.5, target)], &run.cost_model)[0]
+        };
+        let (red, yellow, white) = (run.sources[0], run.sources[1], run.sources[2]);
+        let orange = child(&mut run, red, yellow, FlowerColor::Orange);
+        let purple = child(&mut run, white, white, FlowerColor::Purple);
+        let last = child(&mut run, orange, purple, FlowerColor::Red);
+        (run, last)
+    }
+
+    fn tiles(station: &Station) -> Vec<(usize, usize)> {
+        station.flowers.iter().map(|p| p.position).chain(station.spawn_tiles.iter().copied()).collect()
+    }
+
+    #[test]
+    fn blocks_never_touch() {
+        let (run, last) = three_crosses();
+        for (width, height) in [(16, 16), (14, 3), (3, 14)] {
+            let layout = layout(&run.arena, last, width, height).unwrap();
+            assert_eq!(layout.stations.len(), 3);
+            for (i, a) in layout.stations.iter().enumerate() {
+                for b in &layout.stations[i + 1..] {
+                    for (ax, ay) in tiles(a) {
+                        for (bx, by) in tiles(b) {
+                            assert!(ax.abs_diff(bx) > 1 || ay.abs_diff(by) > 1);
+                        }
+                    }
+                }
+                assert!(tiles(a).iter().all(|&(x, y)| x < width && y < height));
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_a_garden_too_small() {
+        let (run, last) = three_crosses();
+        assert!(layout(&run.arena, last, 13, 3).is_err());
+    }
+}
@@ -1,72 +1,488 @@
-#[macro_use]
-extern crate lazy_static;
-
+mod arena;
+mod checkpoint;
+mod completion;
+mod constraints;
+mod cost;
+mod dag;
+mod dot;
+mod garden;
 mod genotype;
+mod input;
+mod instructions;
+mod layout;
+mod mdp;
 mod path;
+mod planner;
+mod policy;
+mod purify;
+mod simulate;
 mod distribution;
 mod distribution_helpers;
 mod flowers;
 
+use arena::{Arena, InArena, PathId};
+use dag::Dag;
+use distribution_helpers::BreedContext;
+use input::{GardenRun, MixRun, PurifyRun, Run};
+use purify::Purification;
+use instructions::Style;
+use mdp::Limits;
+use path::Path;
+use checkpoint::Checkpoint;
+use cost::CostModel;
+use planner::{Anytime, CancelToken, Problem, Progress, Search, SearchLimits, SearchOptions, SearchStats, Stop};
+use std::{cell::Cell, time::{Duration, Instant}};
 
-use std::{cmp::Reverse, collections::BinaryHeap, collections::HashMap, sync::Arc, sync::RwLock};
+const USAGE: &str = "usage: hanami <run file> [options]
+       hanami garden <garden file> [--seed <seed>]
+       hanami mix <mix file>
+       hanami purify <purify file>
 
-use distribution::Distribution;
-use flowers::{FlowerColor, FlowerType};
-use genotype::Genotype;
-use path::Path;
-use rayon::prelude::*;
+options:
+    --format <format>       json, dag, dot, text, markdown, policy, mdp, simulation,
+                            completion, layout or layout-json (default json)
+    --depth <crosses>       how far policies look ahead (default 2)
+    --max-new-flowers <flowers>
+                            the most bred flowers an mdp state may hold (default 2)
+    --max-states <states>   the most states the mdp solver explores (default 100000)
+    --runs <runs>           simulated runs (default 10000)
+    --seed <seed>           the random seed for simulations (default 0)
+    --width <tiles>         the width of the garden for layouts (default 16)
+    --height <tiles>        the height of the garden for layouts (default 16)
+    --stats                 print how much work the search did to stderr
+    --dominance             skip flowers that look no better than one already expanded
+    --threads <threads>     how many threads the search breeds on (default one per core)
+    --max-expanded <flowers>
+                            stop the search after expanding this many flowers
+    --time-limit <seconds>  stop the search after this long
+    --max-memory <MB>       stop the search once it holds about this much
+    --max-cost <days>       never consider plans costing more than this
+    --progress              print how the search is going to stderr
+    --anytime               print a quick plan first, then each cheaper one found
+    --checkpoint <file>     save the search to this file now and then, and when stopped
+    --checkpoint-every <seconds>
+                            how often to save the search (default 300)
+    --resume <file>         carry on from a checkpoint made with the same run file";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Dag,
+    Dot,
+    Instructions(Style),
+    Policy,
+    Mdp,
+    Simulation,
+    Completion,
+    Layout,
+    LayoutJson,
+}
 
 fn main() {
-    // sprs::smmp::set_thread_threading_strategy(sprs::smmp::ThreadingStrategy::Fixed(1));
-    let red_seed = Genotype::new(0b11000001);
-    let yellow_seed = Genotype::new(0b00110000);
-    let white_seed = Genotype::new(0b00000100);
-
-    let red_rose_dist = Distribution::new(FlowerType::Rose, red_seed);
-    let yellow_rose_dist = Distribution::new(FlowerType::Rose, yellow_seed);
-    let white_rose_dist = Distribution::new(FlowerType::Rose, white_seed);
-
-    let red_rose_path = Arc::new(Path::new("red seed".to_string(), red_rose_dist.clone()));
-    let yellow_rose_path = Arc::new(Path::new("yellow seed".to_string(), yellow_rose_dist.clone()));
-    let white_rose_path = Arc::new(Path::new("white seed".to_string(), white_rose_dist.clone()));
-
-    let mut processed: Vec<Arc<Path>> = Vec::new();
-    let mut upcoming: BinaryHeap<Reverse<Arc<Path>>> = BinaryHeap::new();
-    let mut visited: HashMap<Distribution, Arc<Path>> = HashMap::new();
-
-    upcoming.push(Reverse(red_rose_path.clone()));
-    upcoming.push(Reverse(yellow_rose_path.clone()));
-    upcoming.push(Reverse(white_rose_path.clone()));
-
-    loop {
-        let new_path: Arc<Path>;
-        if let Some(Reverse(path)) = upcoming.pop() {
-            new_path = path;
-        } else {
-            break;
+    let mut file = None;
+    let mut format = Format::Json;
+    let mut depth = 2;
+    let mut max_new_flowers = 2;
+    let mut max_states = 100_000;
+    let mut runs = 10_000;
+    let mut seed = 0;
+    let mut width = 16;
+    let mut height = 16;
+    let mut show_stats = false;
+    let mut dominance = false;
+    let mut threads = None;
+    let mut limits = SearchLimits::default();
+    let mut show_progress = false;
+    let mut anytime = false;
+    let mut checkpoint = None;
+    let mut checkpoint_every = Duration::from_secs(300);
+    let mut resume = None;
+    let mut args = std::env::args().skip(1).peekable();
+    let command = args.next_if(|arg| arg == "garden" || arg == "mix" || arg == "purify");
+    while let Some(arg) = args.next() {
+        // a garden is simulated, not searched, so it has no use for the rest.
+        if command.as_deref() == Some("garden") && arg.starts_with("--") && arg != "--seed" {
+            eprintln!("hanami garden only takes --seed, not {}", arg);
+            std::process::exit(2);
+        }
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("json") => Format::Json,
+                    Some("dag") => Format::Dag,
+                    Some("dot") => Format::Dot,
+                    Some("text") => Format::Instructions(Style::Text),
+                    Some("markdown") => Format::Instructions(Style::Markdown),
+                    Some("policy") => Format::Policy,
+                    Some("mdp") => Format::Mdp,
+                    Some("simulation") => Format::Simulation,
+                    Some("completion") => Format::Completion,
+                    Some("layout") => Format::Layout,
+                    Some("layout-json") => Format::LayoutJson,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--depth" => {
+                depth = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0 => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--max-new-flowers" => {
+                max_new_flowers = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0 => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--max-states" => {
+                max_states = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0 => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--runs" => {
+                runs = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0 => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--seed" => {
+                seed = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--width" => {
+                width = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--height" => {
+                height = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) => d,
+                    _ => exit_with_usage(),
+                }
+            }
+            "--stats" => show_stats = true,
+            "--dominance" => dominance = true,
+            "--threads" => {
+                threads = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0 => Some(d),
+                    _ => exit_with_usage(),
+                }
+            }
+            "--max-expanded" => {
+                limits.max_expanded = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0 => Some(d),
+                    _ => exit_with_usage(),
+                }
+            }
+            "--time-limit" => {
+                limits.time_limit = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0f64 => Some(Duration::from_secs_f64(d)),
+                    _ => exit_with_usage(),
+                }
+            }
+            "--max-memory" => {
+                limits.max_memory = match args.next().and_then(|d| d.parse::<usize>().ok()) {
+                    Some(d) if d > 0 => Some(d * 1024 * 1024),
+                    _ => exit_with_usage(),
+                }
+            }
+            "--max-cost" => {
+                limits.max_cost = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0f32 => Some(d),
+                    _ => exit_with_usage(),
+                }
+            }
+            "--progress" => show_progress = true,
+            "--anytime" => anytime = true,
+            "--checkpoint" => checkpoint = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--checkpoint-every" => {
+                checkpoint_every = match args.next().and_then(|d| d.parse().ok()) {
+                    Some(d) if d > 0f64 => Duration::from_secs_f64(d),
+                    _ => exit_with_usage(),
+                }
+            }
+            "--resume" => resume = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => exit_with_usage(),
         }
+    }
+    let file = file.unwrap_or_else(|| exit_with_usage());
+    if anytime && (checkpoint.is_some() || resume.is_some()) {
+        eprintln!("--anytime can't be used with --checkpoint or --resume");
+        std::process::exit(2);
+    }
+    if let Some(threads) = threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().unwrap();
+    }
 
-        if visited.contains_key(&new_path.target) {
-            continue;
-        } else {
-            visited.insert(new_path.target.clone(), new_path.clone());
+    if command.as_deref() == Some("mix") {
+        let run = match MixRun::load(&file) {
+            Ok(run) => run,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let partners: Vec<_> = run.partners.iter().map(|(weight, d)| (*weight, d)).collect();
+        let outcomes: Vec<_> = run.flower.breed_mixture(&partners, &BreedContext::new(run.flower.flower_type)).into_iter().map(|(probability, child)| {
+            // each genotype's chance is out of the children of this color.
+            let genotypes: Vec<_> = child.genotypes().map(|(genotype, probability)| serde_json::json!({
+                "genotype": format!("{:?}", genotype),
+                "probability": probability,
+            })).collect();
+            serde_json::json!({
+                "color": child.flower_color,
+                "probability": probability,
+                "genotypes": genotypes,
+            })
+        }).collect();
+        println!("{}", serde_json::to_string_pretty(&outcomes).unwrap());
+        return;
+    }
+
+    if command.as_deref() == Some("purify") {
+        let run = match PurifyRun::load(&file) {
+            Ok(run) => run,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        match Purification::new(&run.flower, run.genotype, &run.testers, run.copy, &run.cost_model) {
+            Some(purification) => println!("{}", serde_json::to_string_pretty(&purification).unwrap()),
+            None => {
+                eprintln!("the flower can't be purified into {:?}", run.genotype);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if command.as_deref() == Some("garden") {
+        let mut run = match GardenRun::load(&file) {
+            Ok(run) => run,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let births = run.garden.simulate(run.days, run.breed_rate, seed);
+        println!("{}", serde_json::to_string_pretty(&births).unwrap());
+        return;
+    }
+
+    let mut run = match Run::load(&file) {
+        Ok(run) => run,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let context = BreedContext::new(run.flower_type);
+    let policy = match format {
+        Format::Policy => Some(policy::build(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, depth, &context)),
+        Format::Mdp => {
+            let limits = Limits {
+                max_new_flowers,
+                max_states,
+            };
+            Some(mdp::solve(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, &limits, &context))
+        }
+        _ => None,
+    };
+    if let Some(policy) = policy {
+        match policy {
+            Some(policy) => println!("{}", serde_json::to_string_pretty(&InArena(&run.arena, &policy)).unwrap()),
+            None => exit_unreachable(&run),
+        }
+        return;
+    }
+
+    // the first ctrl-c stops the search and prints the best plan found so
+    // far, a second one quits.
+    let cancel = CancelToken::default();
+    let handler_cancel = cancel.clone();
+    ctrlc::set_handler(move || {
+        if handler_cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        handler_cancel.cancel();
+    }).unwrap();
+    let last_progress = Cell::new(None);
+    let print_progress = |progress: &Progress| {
+        if last_progress.get().is_some_and(|last: Instant| last.elapsed() < Duration::from_secs(1)) {
+            return;
         }
+        last_progress.set(Some(Instant::now()));
+        let best = match progress.best {
+            Some(best) => format!("{} days", best),
+            None => "none".to_string(),
+        };
+        eprintln!(
+            "expanded {} flowers, {} queued, now at {} days, best plan so far: {}",
+            progress.expanded, progress.frontier, progress.cost, best,
+        );
+    };
+    let options = SearchOptions {
+        dominance,
+        limits,
+        cancel: Some(&cancel),
+        progress: if show_progress { Some(&print_progress) } else { None },
+    };
+    let problem = Problem {
+        target: &run.target,
+        cost_model: &run.cost_model,
+        constraints: &run.constraints,
+        context: &context,
+    };
+    let output = Output { format, runs, seed, width, height };
+    if anytime {
+        let mut plans = Anytime::new(&mut run.arena, &run.sources, problem, options);
+        let mut found = false;
+        let mut proven = false;
+        while let Some(plan) = plans.next() {
+            found = true;
+            proven = plan.proven_optimal;
+            if plan.proven_optimal {
+                eprintln!("found a plan costing {} days, proven the cheapest", plan.cost);
+            } else {
+                eprintln!("found a plan costing {} days", plan.cost);
+            }
+            if let Err(e) = print_plan(plans.arena(), plan.path, &run.cost_model, &output) {
+                eprintln!("{}", e);
+            }
+        }
+        let stats = plans.stats().clone();
+        finish_search(&run, &stats, found, show_stats, &limits);
+        if found && !proven && stats.stopped.is_none() {
+            if dominance {
+                eprintln!("the last plan isn't proven the cheapest, since --dominance may have skipped a flower the cheapest plan needs");
+            } else {
+                eprintln!("the last plan isn't proven the cheapest");
+            }
+        }
+        return;
+    }
 
-        println!("{:?}", new_path.target.flower_color);
-        if new_path.target.flower_color == FlowerColor::Blue {
-            let j = serde_json::to_string(new_path.as_ref()).unwrap();
-            println!("{}", j);
+    let mut search = match &resume {
+        Some(resume) => {
+            let resumed = match Checkpoint::load(resume) {
+                Ok(checkpoint) => checkpoint.resume(resume, &mut run.arena, &run.sources, problem, options),
+                Err(e) => Err(e),
+            };
+            match resumed {
+                Ok(search) => search,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => Search::new(&run.arena, &run.sources, problem, options),
+    };
+    let save = |arena: &Arena, search: &Search| {
+        if let Some(checkpoint) = &checkpoint {
+            if let Err(e) = Checkpoint::save(checkpoint, arena, search) {
+                eprintln!("could not save {}: {}", checkpoint, e);
+            }
         }
+    };
+    let mut last_save = Instant::now();
+    let path = search.run(&mut run.arena, |arena, search| {
+        if last_save.elapsed() >= checkpoint_every {
+            save(arena, search);
+            last_save = Instant::now();
+        }
+    });
+    // a stopped search is saved so it can be resumed.
+    if search.stats.stopped.is_some() {
+        save(&run.arena, &search);
+    }
+    finish_search(&run, &search.stats, path.is_some(), show_stats, &limits);
+    if let Err(e) = print_plan(&run.arena, path.unwrap(), &run.cost_model, &output) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-        processed.push(new_path.clone());
-        let new_paths = processed.par_iter().flat_map_iter(|p| {
-            new_path.clone().breed(p.clone())
-        });
-        // let new_paths = new_paths.filter(|p| {
-        //     p.target.inner.indices().len() < 3
-        // });
-        let new_paths = new_paths.map(|p| Reverse(p));
+// how to print a plan.
+struct Output {
+    format: Format,
+    runs: usize,
+    seed: u64,
+    width: usize,
+    height: usize,
+}
 
-        upcoming.par_extend(new_paths);
+fn print_plan(arena: &Arena, path: PathId, cost_model: &CostModel, output: &Output) -> Result<(), String> {
+    match output.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&InArena(arena, &path)).unwrap()),
+        Format::Dag => {
+            let steps = Path::unique_steps(arena, path);
+            println!("{}", serde_json::to_string_pretty(&Dag::new(arena, &steps)).unwrap())
+        }
+        Format::Dot => print!("{}", dot::to_dot(arena, path)),
+        Format::Instructions(style) => print!("{}", instructions::render(arena, path, style)),
+        Format::Simulation => {
+            let report = simulate::simulate(arena, path, cost_model, output.runs, output.seed);
+            if report.days.is_none() {
+                eprintln!("no run finished, so there are no days to report");
+            }
+            println!("{}", serde_json::to_string_pretty(&report).unwrap())
+        }
+        Format::Completion => {
+            let report = completion::Report::new(arena, path, cost_model);
+            println!("{}", serde_json::to_string_pretty(&report).unwrap())
+        }
+        Format::Layout => print!("{}", layout::layout(arena, path, output.width, output.height)?.to_ascii()),
+        Format::LayoutJson => {
+            let layout = layout::layout(arena, path, output.width, output.height)?;
+            println!("{}", serde_json::to_string_pretty(&layout).unwrap())
+        }
+        Format::Policy | Format::Mdp => unreachable!(),
     }
+    Ok(())
+}
+
+// reports how the search went, and exits if it found no plan.
+fn finish_search(run: &Run, stats: &SearchStats, found: bool, show_stats: bool, limits: &SearchLimits) {
+    if show_stats {
+        eprintln!("{}", serde_json::to_string_pretty(stats).unwrap());
+    }
+    if let Some(stop) = stats.stopped {
+        let reason = match stop {
+            Stop::Expanded => "after expanding --max-expanded flowers",
+            Stop::Time => "at the time limit",
+            Stop::Memory => "at the memory limit",
+            Stop::Cancelled => "when cancelled",
+        };
+        if found {
+            eprintln!("the search stopped {}, so this plan may not be the quickest", reason);
+        } else {
+            eprintln!("the search stopped {} before finding a plan", reason);
+            std::process::exit(1);
+        }
+    }
+    if !found {
+        if limits.max_cost.is_some() {
+            eprintln!("no {:?} {:?} can be bred from these flowers within --max-cost days", run.target.color, run.flower_type);
+            std::process::exit(1);
+        }
+        exit_unreachable(run);
+    }
+}
+
+fn exit_unreachable(run: &Run) -> ! {
+    eprintln!("no {:?} {:?} can be bred from these flowers", run.target.color, run.flower_type);
+    std::process::exit(1);
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
 }
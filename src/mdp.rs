@@ -0,0 +1,418 @@
+use std::{collections::HashMap, collections::VecDeque};
+
+use crate::{arena::{Arena, PathId}, constraints::Constraints, cost::CostModel, distribution::{Distribution, Exact}, distribution_helpers::BreedContext, path::Path, planner::Target, policy::{Next, Outcome, Policy, Start, describe, keep_best}};
+
+// how much of the state space the solver may explore. a state is the set of
+// flowers owned, so without caps roses never run out of states.
+pub struct Limits {
+    // the most bred flowers a state may hold on top of the starting ones.
+    pub max_new_flowers: usize,
+    pub max_states: usize,
+}
+
+// what a child of a cross leads to.
+#[derive(Clone, Copy)]
+enum Transition {
+    Goal,
+    // the child can't or needn't be kept, so the state doesn't change.
+    Stay,
+    Move(usize),
+}
+
+// a cross between two owned flowers, or cloning the only copy of one so
+// it can be crossed with itself.
+struct Action {
+    pair: (usize, usize),
+    clone: bool,
+    outcomes: Vec<(f32, Distribution, Transition)>,
+}
+
+struct State {
+    owned: Vec<usize>,
+    actions: Vec<Action>,
+    value: f32,
+    best: Option<usize>,
+}
+
+// a markov decision process over sets of owned flowers. every action is a
+// cross between two owned flowers that takes a day to produce a child, and
+// its transitions are the colors that child may be, or else the clone a
+// lone flower needs before it can be crossed with itself. states only ever
+// grow, so apart from discarded children it is acyclic.
+struct Mdp<'a> {
+    target: &'a Target,
+    constraints: &'a Constraints,
+    limits: &'a Limits,
+    context: &'a BreedContext,
+    arena: &'a mut Arena,
+    day: f32,
+    clone_days: f32,
+    flowers: Vec<PathId>,
+    // flowers are the same only if every chance is, so a flower reached a
+    // second way with other chances gets its own transitions. a flower
+    // with a copy is apart from the only copy of it.
+    flower_ids: HashMap<(Exact, bool), usize>,
+    crosses: HashMap<(usize, usize), Vec<(f32, Distribution)>>,
+    states: Vec<State>,
+    state_ids: HashMap<Vec<usize>, usize>,
+}
+
+// finds the strategy with the least expected days to the target by value
+// iteration, within the given limits. returns None if the target can't be
+// reached inside the limits.
+pub fn solve(
+    arena: &mut Arena,
+    owned: &[PathId],
+    target: &Target,
+    cost_model: &CostModel,
+    constraints: &Constraints,
+    limits: &Limits,
+    context: &BreedContext,
+) -> Option<Policy> {
+    if let Some(policy) = Policy::owned(arena, owned, target) {
+        return Some(policy);
+    }
+    let mut mdp = Mdp::explored(arena, owned, target, cost_model, constraints, limits, context)?;
+    mdp.iterate();
+    if !mdp.states[0].value.is_finite() {
+        return None;
+    }
+    Some(mdp.policy(0))
+}
+
+impl<'a> Mdp<'a> {
+    // every state reachable from the owned flowers within the limits, or
+    // None if the target is already owned.
+    fn explored(
+        arena: &'a mut Arena,
+        owned: &[PathId],
+        target: &'a Target,
+        cost_model: &CostModel,
+        constraints: &'a Constraints,
+        limits: &'a Limits,
+        context: &'a BreedContext,
+    ) -> Option<Self> {
+        let mut mdp = Mdp {
+            target,
+            constraints,
+            limits,
+            context,
+            arena,
+            day: cost_model.breed_days(1f32),
+            clone_days: cost_model.clone_days(),
+            flowers: Vec::new(),
+            flower_ids: HashMap::new(),
+            crosses: HashMap::new(),
+            states: Vec::new(),
+            state_ids: HashMap::new(),
+        };
+
+        let mut initial = Vec::new();
+        for &path in owned {
+            if !constraints.allows(mdp.arena, &mdp.arena[path]) {
+                continue;
+            }
+            if target.is_met_by(mdp.arena.target(path)) {
+                return None;
+            }
+            let id = mdp.intern(path);
+            if !initial.contains(&id) {
+                initial.push(id);
+            }
+        }
+        initial.sort_unstable();
+
+        mdp.explore(initial);
+        Some(mdp)
+    }
+
+    fn intern(&mut self, path: PathId) -> usize {
+        let key = (Exact(*self.arena.target(path)), self.arena[path].is_single());
+        if let Some(&id) = self.flower_ids.get(&key) {
+            return id;
+        }
+        self.flower_ids.insert(key, self.flowers.len());
+        self.flowers.push(path);
+        self.flowers.len() - 1
+    }
+
+    // a bred flower, or the copy of one, only gets a path the first time it
+    // turns up.
+    fn intern_child(&mut self, child: Distribution, single: bool) -> usize {
+        if let Some(&id) = self.flower_ids.get(&(Exact(child), single)) {
+            return id;
+        }
+        let child_id = self.arena.intern(child);
+        let path = match single {
+            true => Path::new_single(describe(&child), child_id),
+            false => Path::new(describe(&child), child_id),
+        };
+        let path = self.arena.add(path);
+        self.intern(path)
+    }
+
+    fn is_single(&self, flower: usize) -> bool {
+        self.arena[self.flowers[flower]].is_single()
+    }
+
+    // the state with next in it, queueing the state if it is new. None once
+    // the state cap is hit.
+    fn move_to(&mut self, next: Vec<usize>, queue: &mut VecDeque<usize>) -> Option<usize> {
+        let known = self.state_ids.contains_key(&next);
+        let next = self.add_state(next)?;
+        if !known {
+            queue.push_back(next);
+        }
+        Some(next)
+    }
+
+    fn add_state(&mut self, owned: Vec<usize>) -> Option<usize> {
+        if let Some(&id) = self.state_ids.get(&owned) {
+            return Some(id);
+        }
+        if self.states.len() >= self.limits.max_states {
+            return None;
+        }
+        self.state_ids.insert(owned.clone(), self.states.len());
+        self.states.push(State {
+            owned,
+            actions: Vec::new(),
+            value: f32::INFINITY,
+            best: None,
+        });
+        Some(self.states.len() - 1)
+    }
+
+    // breadth first, so that when the state cap is hit it's the states
+    // needing the most crosses that are left out.
+    fn explore(&mut self, initial: Vec<usize>) {
+        let initial_len = initial.len();
+        self.add_state(initial);
+        let mut queue = VecDeque::from(vec![0]);
+
+        while let Some(state) = queue.pop_front() {
+            let owned = self.states[state].owned.clone();
+            let can_keep = owned.len() - initial_len < self.limits.max_new_flowers;
+
+            let mut actions = Vec::new();
+            for (i, &left) in owned.iter().enumerate() {
+                // the only copy of a flower is cloned before it is crossed
+                // with itself, and the copy is kept in its place.
+                if self.is_single(left) {
+                    let flower = *self.arena.target(self.flowers[left]);
+                    let copy = self.intern_child(flower, false);
+                    let mut next: Vec<_> = owned.iter().map(|&f| if f == left { copy } else { f }).collect();
+                    next.sort_unstable();
+                    if let Some(next) = self.move_to(next, &mut queue) {
+                        let outcomes = vec![(1f32, flower, Transition::Move(next))];
+                        actions.push(Action { pair: (left, left), clone: true, outcomes });
+                    }
+                }
+                for &right in &owned[i..] {
+                    if right == left && self.is_single(left) {
+                        continue;
+                    }
+                    let mut outcomes = Vec::new();
+                    for (probability, child) in self.cross(left, right) {
+                        let transition = if self.target.is_met_by(&child) {
+                            Transition::Goal
+                        } else if !can_keep || !self.constraints.allows_distribution(&child) {
+                            Transition::Stay
+                        } else {
+                            let id = self.intern_child(child, true);
+                            let copied = self.flower_ids.get(&(Exact(child), false));
+                            if owned.contains(&id) || copied.is_some_and(|copied| owned.contains(copied)) {
+                                Transition::Stay
+                            } else {
+                                let mut next = owned.clone();
+                                next.push(id);
+                                next.sort_unstable();
+                                match self.move_to(next, &mut queue) {
+                                    Some(next) => Transition::Move(next),
+                                    None => Transition::Stay,
+                                }
+                            }
+                        };
+                        outcomes.push((probability, child, transition));
+                    }
+                    if outcomes.iter().any(|(_, _, t)| !matches!(t, Transition::Stay)) {
+                        actions.push(Action { pair: (left, right), clone: false, outcomes });
+                    }
+                }
+            }
+            self.states[state].actions = actions;
+        }
+    }
+
+    fn cross(&mut self, left: usize, right: usize) -> Vec<(f32, Distribution)> {
+        let (arena, flowers, context) = (&*self.arena, &self.flowers, self.context);
+        self.crosses
+            .entry((left, right))
+            .or_insert_with(|| arena.target(flowers[left]).breed(arena.target(flowers[right]), context))
+            .clone()
+    }
+
+    // sweeps from the largest states down, and among those from the ones
+    // with the most copies, which settles an acyclic mdp in a single pass;
+    // the loop is only there as a guard.
+    fn iterate(&mut self) {
+        let mut order: Vec<usize> = (0..self.states.len()).collect();
+        order.sort_by_key(|&s| {
+            let owned = &self.states[s].owned;
+            std::cmp::Reverse((owned.len(), owned.iter().filter(|&&f| !self.is_single(f)).count()))
+        });
+
+        loop {
+            let mut changed = false;
+            for &s in &order {
+                let mut best = (f32::INFINITY, None);
+                for (a, action) in self.states[s].actions.iter().enumerate() {
+                    let (value, _) = self.value(action);
+                    if value < best.0 {
+                        best = (value, Some(a));
+                    }
+                }
+                let state = &mut self.states[s];
+                if best.0 < state.value - 1e-4 {
+                    changed = true;
+                }
+                state.value = best.0;
+                state.best = best.1;
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    // the expected time of an action and which children to keep. the copy
+    // always turns up eventually.
+    fn value(&self, action: &Action) -> (f32, Vec<bool>) {
+        let remaining = self.remaining_times(action);
+        match action.clone {
+            true => (self.clone_days + remaining[0].1, vec![true]),
+            false => keep_best(&remaining, self.day),
+        }
+    }
+
+    fn remaining_times(&self, action: &Action) -> Vec<(f32, f32)> {
+        action.outcomes.iter().map(|(probability, _, transition)| {
+            let remaining_time = match transition {
+                Transition::Goal => 0f32,
+                Transition::Stay => f32::INFINITY,
+                Transition::Move(next) => self.states[*next].value,
+            };
+            (*probability, remaining_time)
+        }).collect()
+    }
+
+    fn policy(&self, state: usize) -> Policy {
+        let action = &self.states[state].actions[self.states[state].best.unwrap()];
+        let (_, kept) = self.value(action);
+        let outcomes = action.outcomes.iter().zip(kept).map(|((probability, child, transition), kept)| {
+            let next = match (transition, kept) {
+                (Transition::Goal, true) => Next::Done,
+                (Transition::Move(next), true) => Next::Policy(Box::new(self.policy(*next))),
+                _ => Next::Discard,
+            };
+            Outcome {
+                probability: *probability,
+                child: *child,
+                next,
+            }
+        });
+
+        let (left, right) = (self.flowers[action.pair.0], self.flowers[action.pair.1]);
+        Policy {
+            start: if action.clone { Start::Clone(left) } else { Start::Cross(left, right) },
+            expected_time: self.states[state].value,
+            outcomes: outcomes.collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Run;
+
+    const PURPLE_ROSE: &str = r#"{
+        "species": "Rose",
+        "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+        "target": { "color": "Purple", "genotypes": ["rryywwss", "rryYwwss"] }
+    }"#;
+
+    const LIMITS: Limits = Limits {
+        max_new_flowers: 2,
+        max_states: 10_000,
+    };
+
+    #[test]
+    fn moves_to_the_flower_that_was_bred() {
+        let mut run = Run::from_json(PURPLE_ROSE);
+        let context = BreedContext::new(run.flower_type);
+        let mdp = Mdp::explored(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, &LIMITS, &context).unwrap();
+        let mut moves = 0;
+        for state in &mdp.states {
+            for action in &state.actions {
+                for (_, child, transition) in &action.outcomes {
+                    if let Transition::Move(next) = transition {
+                        let bred = mdp.states[*next].owned.iter().find(|f| !state.owned.contains(f)).unwrap();
+                        assert!(Exact(*mdp.arena.target(mdp.flowers[*bred])) == Exact(*child));
+                        moves += 1;
+                    }
+                }
+            }
+        }
+        assert!(moves > 0);
+    }
+
+    #[test]
+    fn adds_a_path_only_for_new_flowers() {
+        let mut run = Run::from_json(PURPLE_ROSE);
+        let context = BreedContext::new(run.flower_type);
+        let paths = run.arena.path_count();
+        let mdp = Mdp::explored(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, &LIMITS, &context).unwrap();
+        assert_eq!(mdp.arena.path_count() - paths, mdp.flowers.len() - run.sources.len());
+    }
+
+    #[test]
+    fn solves_a_purple_rose() {
+        let mut run = Run::from_json(PURPLE_ROSE);
+        let context = BreedContext::new(run.flower_type);
+        let policy = solve(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, &LIMITS, &context).unwrap();
+        assert!(policy.expected_time.is_finite() && policy.expected_time >= 1f32);
+        let total: f32 = policy.outcomes.iter().map(|o| o.probability).sum();
+        assert!((total - 1f32).abs() < 1e-4);
+    }
+
+    #[test]
+    fn does_nothing_when_the_target_is_owned() {
+        let mut run = Run::from_json(r#"{
+            "species": "Rose",
+            "flowers": [{ "seed": "white" }],
+            "target": { "color": "White" }
+        }"#);
+        let context = BreedContext::new(run.flower_type);
+        let policy = solve(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, &LIMITS, &context).unwrap();
+        assert!(matches!(policy.start, Start::Owned(_)));
+        assert_eq!(policy.expected_time, 0f32);
+    }
+
+    // the only copy of a flower has to be cloned before it can be crossed
+    // with itself, and the clone's wait is part of the expected time.
+    #[test]
+    fn clones_a_lone_flower_before_crossing_it_with_itself() {
+        let mut run = Run::from_json(r#"{
+            "species": "Rose",
+            "flowers": [{ "genotype": "rryyWwss", "copies": 1 }],
+            "target": { "color": "Purple" }
+        }"#);
+        let context = BreedContext::new(run.flower_type);
+        let policy = solve(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, &LIMITS, &context).unwrap();
+        assert!(matches!(policy.start, Start::Clone(original) if original == run.sources[0]));
+        let Next::Policy(cross) = &policy.outcomes[0].next else { panic!() };
+        assert!(matches!(cross.start, Start::Cross(left, right) if left == right && !run.arena[left].is_single()));
+        assert_eq!(policy.expected_time, run.cost_model.clone_days() + cross.expected_time);
+    }
+}
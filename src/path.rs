@@ -1,103 +1,407 @@
-use std::{cmp::Ordering, fmt::Debug, sync::Arc};
-use serde::ser::{Serialize, SerializeStruct};
-
-use crate::distribution::Distribution;
+use std::{cmp::Ordering, collections::HashMap};
+use serde::{Deserialize, Serialize, ser::SerializeStruct};
 
+use crate::{arena::{Arena, DistributionId, InArena, PathId}, completion::Completion, cost::{CostModel, Objective}, distribution::Distribution, distribution_helpers::BreedContext, genotype::Genotype, input::parse_genotype, purify::Purification};
 
+// one step of a plan, kept in an arena with its parents given by id.
 pub struct Path {
-    pub target: Distribution,
+    pub target: DistributionId,
     pub expected_time: f32,
+    // what the search orders paths by, which is expected_time unless the
+    // cost model asks for a percentile.
+    pub cost: f32,
+    // the cost of the later parent, which breaks ties in the search.
+    pub parent_cost: f32,
+    pub generation: usize,
+    // whether this is the only copy of the flower, so crossing it with
+    // itself needs a clone first. seeds can always be bought again.
+    single: bool,
+    // the chance a child of the parents is the target color.
+    probability: f32,
+    // only kept when the cost model needs it, once the path is expanded.
+    completion: Option<Completion>,
     source: PathSource,
 }
 
+// a path in a flattened plan, with its parents as indices into the plan.
+pub type Step = (PathId, StepSource);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepSource {
+    Owned,
+    Breed(usize, usize),
+    Clone(usize),
+    Purify(usize),
+}
+
 enum PathSource {
     Label(String),
-    Breed((Arc<Path>, Arc<Path>))
+    Breed(PathId, PathId),
+    Clone(PathId),
+    Purify(PathId, Box<Purification>),
+}
+
+// a path as stored in a checkpoint. floats are kept as their bit patterns
+// so the search picks up exactly where it was. a purification is stored by
+// what it was worked out from and worked out again when loaded.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PathRecord {
+    target: DistributionId,
+    expected_time: u32,
+    cost: u32,
+    parent_cost: u32,
+    generation: usize,
+    single: bool,
+    probability: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completion: Option<Vec<u64>>,
+    source: SourceRecord,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+enum SourceRecord {
+    Label(String),
+    Breed(PathId, PathId),
+    Clone(PathId),
+    #[serde(rename_all = "camelCase")]
+    Purify {
+        original: PathId,
+        genotype: String,
+        testers: Vec<String>,
+    },
 }
 
 impl Path {
-    pub fn new(label: String, distribution: Distribution) -> Self {
-        Self { 
-            target: distribution,
+    pub fn new(label: String, target: DistributionId) -> Self {
+        Self {
+            target,
             expected_time: 0f32,
+            cost: 0f32,
+            parent_cost: 0f32,
+            generation: 0,
+            single: false,
+            probability: 1f32,
+            completion: None,
             source: PathSource::Label(label),
         }
     }
 
-    pub fn breed(self: Arc<Self>, other: Arc<Self>) -> impl Iterator<Item = Arc<Path>> {
-        let mut parent_cost = self.expected_time;
-        if parent_cost.partial_cmp(&other.expected_time).unwrap() == Ordering::Less {
-            parent_cost = other.expected_time;
+    // a flower there is only one of.
+    pub fn new_single(label: String, target: DistributionId) -> Self {
+        Self {
+            single: true,
+            ..Self::new(label, target)
         }
-        let mut new_dists = self.target.breed(&other.target);
-        new_dists.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap().reverse());
-        new_dists.into_iter().map(move |(probability, distribution)| Arc::new(Self {
-            target: distribution,
-            expected_time: parent_cost + 1f32 / probability,
-            source: PathSource::Breed((self.clone(), other.clone())),
-        }))
     }
 
-    fn parent_cost(&self) -> f32{
-        match &self.source {
-            PathSource::Label(_) => 0f32,
-            PathSource::Breed((left, right)) => {
-                let mut parent_cost = left.expected_time;
-                if parent_cost.partial_cmp(&right.expected_time).unwrap() == Ordering::Less {
-                    parent_cost = right.expected_time;
-                }
-                parent_cost
+    // the flower and a copy of it, made by leaving it without partners until
+    // it spreads on its own.
+    pub fn clone_of(arena: &mut Arena, id: PathId, cost_model: &CostModel) -> PathId {
+        let original = &arena[id];
+        let expected_time = original.expected_time + cost_model.clone_days();
+        let (cost, completion) = match cost_model.objective {
+            Objective::Mean => (expected_time, None),
+            Objective::Percentile(fraction) => {
+                let completion = Completion::of_path(arena, id, cost_model).wait(cost_model.clone_rate as f64);
+                (completion.percentile(fraction as f64) as f32, Some(completion))
             }
+        };
+        let path = Self {
+            target: original.target,
+            expected_time,
+            cost,
+            parent_cost: original.cost,
+            generation: original.generation,
+            single: false,
+            probability: 1f32,
+            completion,
+            source: PathSource::Clone(id),
+        };
+        arena.add(path)
+    }
+
+    // the flower crossed with copies of itself or the testers until it is
+    // most likely the given genotype, or None if that can't happen.
+    pub fn purify(arena: &mut Arena, id: PathId, genotype: Genotype, testers: &[Genotype], cost_model: &CostModel) -> Option<PathId> {
+        let original = &arena[id];
+        let flower = arena.target(id);
+        let purification = Purification::new(flower, genotype, testers, !original.single, cost_model)?;
+        let days = purification.expected_time;
+        let expected_time = original.expected_time + days;
+        let (cost, completion) = match cost_model.objective {
+            Objective::Mean => (expected_time, None),
+            Objective::Percentile(fraction) => {
+                let completion = Completion::of_path(arena, id, cost_model).purify(days);
+                (completion.percentile(fraction as f64) as f32, Some(completion))
+            }
+        };
+        let (parent_cost, generation) = (original.cost, original.generation + 1);
+        let target = arena.intern(Distribution::new(flower.flower_type, genotype));
+        let path = Self {
+            target,
+            expected_time,
+            cost,
+            parent_cost,
+            generation,
+            single: true,
+            probability: 1f32,
+            completion,
+            source: PathSource::Purify(id, Box::new(purification)),
+        };
+        Some(arena.add(path))
+    }
+
+    // the colors a child of the two may be, most likely first. this only
+    // reads the arena, so many crosses can be worked out at once.
+    pub fn cross(arena: &Arena, left: PathId, right: PathId, context: &BreedContext) -> Vec<(f32, Distribution)> {
+        let (left, right) = (arena.target(left), arena.target(right));
+        let mut children = left.breed(right, context);
+        // a pair of black roses may instead be watered for gold.
+        children.extend(left.breed_golden(right, context));
+        children.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap().reverse());
+        children
+    }
+
+    // adds a path for every child of the cross, as remembered by the arena
+    // from Path::cross.
+    pub fn breed(arena: &mut Arena, left: PathId, right: PathId, children: &[(f32, DistributionId)], cost_model: &CostModel) -> Vec<PathId> {
+        // crossing the only copy of a flower with itself needs a clone.
+        let right = if left == right && arena[left].single {
+            Self::clone_of(arena, left, cost_model)
+        } else {
+            right
+        };
+        let (left_path, right_path) = (&arena[left], &arena[right]);
+        let mut parent_time = left_path.expected_time;
+        if parent_time.partial_cmp(&right_path.expected_time).unwrap() == Ordering::Less {
+            parent_time = right_path.expected_time;
         }
+        let mut parent_cost = left_path.cost;
+        if parent_cost.partial_cmp(&right_path.cost).unwrap() == Ordering::Less {
+            parent_cost = right_path.cost;
+        }
+        let generation = left_path.generation.max(right_path.generation) + 1;
+        let parents = match cost_model.objective {
+            Objective::Mean => None,
+            Objective::Percentile(_) => {
+                let left_completion = Completion::of_path(arena, left, cost_model);
+                let right_completion = Completion::of_path(arena, right, cost_model);
+                Some(Completion::parents(arena, left, right, &left_completion, &right_completion))
+            }
+        };
+
+        let children: Vec<_> = children
+            .iter()
+            .map(|&(probability, target)| {
+                let expected_time = parent_time + cost_model.breed_days(probability);
+                // the chances themselves are left until the child is
+                // expanded, if it ever is.
+                let cost = match (cost_model.objective, &parents) {
+                    (Objective::Percentile(fraction), Some(parents)) => {
+                        parents.wait_percentile((cost_model.breed_rate * probability) as f64, fraction as f64) as f32
+                    }
+                    _ => expected_time,
+                };
+                (target, probability, expected_time, cost)
+            })
+            .collect();
+        children.into_iter().map(|(target, probability, expected_time, cost)| {
+            let path = Self {
+                target,
+                expected_time,
+                cost,
+                parent_cost,
+                generation,
+                single: true,
+                probability,
+                completion: None,
+                source: PathSource::Breed(left, right),
+            };
+            arena.add(path)
+        }).collect()
     }
-}
 
-impl Debug for Path {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    pub fn probability(&self) -> Option<f32> {
         match &self.source {
-            PathSource::Breed((left, right)) => write!(f, "{:?} t={:?} ({:?})-({:?})", self.target.flower_color, self.expected_time, left, right),
-            PathSource::Label(label) => write!(f, "{:?} t={:?}", label, self.expected_time)
+            PathSource::Breed(_, _) => Some(self.probability),
+            _ => None,
+        }
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.single
+    }
+
+    pub fn completion(&self) -> Option<&Completion> {
+        self.completion.as_ref()
+    }
+
+    // keeps the chances of finishing on each day, which are worked out
+    // once the path is expanded.
+    pub fn set_completion(&mut self, completion: Completion) {
+        self.completion = Some(completion);
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match &self.source {
+            PathSource::Label(label) => Some(label),
+            _ => None,
         }
     }
-}
 
-impl Ord for Path {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.expected_time.partial_cmp(&other.expected_time) {
-            Some(Ordering::Equal) => self.parent_cost().partial_cmp(&other.parent_cost()).unwrap().reverse(),
-            Some(ord) => ord,
-            None => panic!()
+    pub fn parents(&self) -> Option<(PathId, PathId)> {
+        match &self.source {
+            PathSource::Breed(left, right) => Some((*left, *right)),
+            _ => None,
         }
     }
-}
 
-impl PartialOrd for Path {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    pub fn cloned_from(&self) -> Option<PathId> {
+        match &self.source {
+            PathSource::Clone(original) => Some(*original),
+            _ => None,
+        }
     }
-}
-impl Eq for Path {}
-impl PartialEq for Path {
-    fn eq(&self, other: &Self) -> bool {
-        self.cmp(other) == Ordering::Equal
+
+    pub fn purified_from(&self) -> Option<(PathId, &Purification)> {
+        match &self.source {
+            PathSource::Purify(original, purification) => Some((*original, purification)),
+            _ => None,
+        }
+    }
+
+    pub fn to_record(&self) -> PathRecord {
+        let source = match &self.source {
+            PathSource::Label(label) => SourceRecord::Label(label.clone()),
+            PathSource::Breed(left, right) => SourceRecord::Breed(*left, *right),
+            PathSource::Clone(original) => SourceRecord::Clone(*original),
+            PathSource::Purify(original, purification) => SourceRecord::Purify {
+                original: *original,
+                genotype: format!("{:?}", purification.genotype),
+                testers: purification.testers().iter().map(|t| format!("{:?}", t)).collect(),
+            },
+        };
+        PathRecord {
+            target: self.target,
+            expected_time: self.expected_time.to_bits(),
+            cost: self.cost.to_bits(),
+            parent_cost: self.parent_cost.to_bits(),
+            generation: self.generation,
+            single: self.single,
+            probability: self.probability.to_bits(),
+            completion: self.completion.as_ref().map(|c| c.to_bits()),
+            source,
+        }
+    }
+
+    // the path of a record, whose parents must already be in the arena.
+    pub fn from_record(arena: &Arena, record: &PathRecord, cost_model: &CostModel) -> Result<Self, String> {
+        let next = arena.path_count();
+        let known = |id: PathId| match id.index() < next {
+            true => Ok(id),
+            false => Err(format!("path {} refers to path {}, which comes after it", next, id.index())),
+        };
+        if record.target.index() >= arena.distribution_count() {
+            return Err(format!("path {} refers to a distribution that doesn't exist", next));
+        }
+        let source = match &record.source {
+            SourceRecord::Label(label) => PathSource::Label(label.clone()),
+            SourceRecord::Breed(left, right) => PathSource::Breed(known(*left)?, known(*right)?),
+            SourceRecord::Clone(original) => PathSource::Clone(known(*original)?),
+            SourceRecord::Purify { original, genotype, testers } => {
+                let original = known(*original)?;
+                let flower = arena.target(original);
+                let genotype = parse_genotype(&flower.flower_type, genotype)?;
+                let testers = testers.iter().map(|t| parse_genotype(&flower.flower_type, t)).collect::<Result<Vec<_>, _>>()?;
+                let copy = !arena[original].single;
+                let purification = Purification::new(flower, genotype, &testers, copy, cost_model)
+                    .ok_or_else(|| format!("path {} purifies a flower that can't be purified", next))?;
+                PathSource::Purify(original, Box::new(purification))
+            }
+        };
+        Ok(Self {
+            target: record.target,
+            expected_time: f32::from_bits(record.expected_time),
+            cost: f32::from_bits(record.cost),
+            parent_cost: f32::from_bits(record.parent_cost),
+            generation: record.generation,
+            single: record.single,
+            probability: f32::from_bits(record.probability),
+            completion: record.completion.as_ref().map(|bits| Completion::from_bits(bits)),
+            source,
+        })
+    }
+
+    // every distinct path in the plan, parents before children. a path
+    // reachable through several steps appears once; parents and originals
+    // of clones are given as indices into the returned list.
+    pub fn unique_steps(arena: &Arena, id: PathId) -> Vec<Step> {
+        let mut ids = HashMap::new();
+        let mut steps = Vec::new();
+        Self::add_unique_steps(arena, id, &mut ids, &mut steps);
+        steps
+    }
+
+    fn add_unique_steps(
+        arena: &Arena,
+        id: PathId,
+        ids: &mut HashMap<PathId, usize>,
+        steps: &mut Vec<Step>,
+    ) -> usize {
+        if let Some(&step) = ids.get(&id) {
+            return step;
+        }
+        let source = match &arena[id].source {
+            PathSource::Label(_) => StepSource::Owned,
+            PathSource::Breed(left, right) => {
+                StepSource::Breed(Self::add_unique_steps(arena, *left, ids, steps), Self::add_unique_steps(arena, *right, ids, steps))
+            }
+            PathSource::Clone(original) => StepSource::Clone(Self::add_unique_steps(arena, *original, ids, steps)),
+            PathSource::Purify(original, _) => StepSource::Purify(Self::add_unique_steps(arena, *original, ids, steps)),
+        };
+        ids.insert(id, steps.len());
+        steps.push((id, source));
+        steps.len() - 1
     }
 }
 
-impl Serialize for Path {
+impl Serialize for InArena<'_, PathId> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer {
-        
-        match &self.source {
-            PathSource::Label(label) => serializer.serialize_str(&label),
-            PathSource::Breed((left, right)) => {
-                let mut path = serializer.serialize_struct("Path", 4)?;
-                path.serialize_field("color", &self.target.flower_color)?;
-                path.serialize_field("expectedTime", &self.expected_time)?;
-                path.serialize_field("genotypes", &self.target)?;
-                path.serialize_field("parents", &(left.as_ref(), right.as_ref()))?;
-                path.end()
+
+        let InArena(arena, &id) = *self;
+        let path = &arena[id];
+        let target = arena.target(id);
+        match &path.source {
+            PathSource::Label(label) => serializer.serialize_str(label),
+            PathSource::Breed(left, right) => {
+                let mut step = serializer.serialize_struct("Path", 4)?;
+                step.serialize_field("color", &target.flower_color)?;
+                step.serialize_field("expectedTime", &path.expected_time)?;
+                step.serialize_field("genotypes", target)?;
+                step.serialize_field("parents", &(InArena(arena, left), InArena(arena, right)))?;
+                step.end()
+            }
+            PathSource::Clone(original) => {
+                let mut step = serializer.serialize_struct("Path", 4)?;
+                step.serialize_field("color", &target.flower_color)?;
+                step.serialize_field("expectedTime", &path.expected_time)?;
+                step.serialize_field("genotypes", target)?;
+                step.serialize_field("cloneOf", &InArena(arena, original))?;
+                step.end()
+            }
+            PathSource::Purify(original, purification) => {
+                let mut step = serializer.serialize_struct("Path", 5)?;
+                step.serialize_field("color", &target.flower_color)?;
+                step.serialize_field("expectedTime", &path.expected_time)?;
+                step.serialize_field("genotypes", target)?;
+                step.serialize_field("purifiedFrom", &InArena(arena, original))?;
+                step.serialize_field("purification", purification.as_ref())?;
+                step.end()
             }
         }
     }
-}
\ No newline at end of file
+}
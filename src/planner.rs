@@ -0,0 +1,832 @@
+use std::{cmp::{Ordering, Reverse}, collections::{BinaryHeap, HashMap, HashSet}, mem::size_of, sync::{Arc, atomic::{AtomicBool, Ordering as AtomicOrdering}}, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+
+use rayon::prelude::*;
+
+use crate::{arena::{Arena, DistributionId, PathId, unordered}, constraints::Constraints, cost::CostModel, distribution::Distribution, distribution_helpers::BreedContext, flowers::FlowerColor, genotype::Genotype, path::Path};
+
+// the flower a plan should end with. when genotypes are given, the plan
+// must guarantee the final flower is one of them.
+pub struct Target {
+    pub color: FlowerColor,
+    pub genotypes: Option<Vec<Genotype>>,
+}
+
+impl Target {
+    pub fn is_met_by(&self, distribution: &Distribution) -> bool {
+        if distribution.flower_color != self.color {
+            return false;
+        }
+        match &self.genotypes {
+            Some(genotypes) => distribution.genotypes().all(|(g, _)| genotypes.contains(&g)),
+            None => true,
+        }
+    }
+}
+
+// how much work a search did. a cross is looked up for every pair of
+// flowers expanded, and only bred when the same two distributions haven't
+// been crossed before.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SearchStats {
+    // flowers found to be new, up to the one that meets the target. this is
+    // the same however the search is split into batches, though the last
+    // batch's flowers are never bred once the target turns up in it.
+    pub expanded: usize,
+    pub dominated: usize,
+    pub cross_hits: usize,
+    pub cross_misses: usize,
+    // why the search gave up before proving its plan the quickest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<Stop>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stop {
+    Expanded,
+    Time,
+    Memory,
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SearchOptions<'a> {
+    // skip flowers that look no better than one already expanded. this is
+    // a heuristic that can skip the flower the cheapest plan needs, so it is
+    // off unless asked for.
+    pub dominance: bool,
+    pub limits: SearchLimits,
+    pub cancel: Option<&'a CancelToken>,
+    // called before every batch is expanded.
+    pub progress: Option<&'a dyn Fn(&Progress)>,
+}
+
+// once any of these is reached the search stops and returns the cheapest
+// plan it has queued so far, if any. paths costing more than max_cost are
+// never queued at all.
+#[derive(Clone, Copy, Default)]
+pub struct SearchLimits {
+    pub max_expanded: Option<usize>,
+    pub time_limit: Option<Duration>,
+    // in bytes, going by a rough count of what the search holds.
+    pub max_memory: Option<usize>,
+    pub max_cost: Option<f32>,
+}
+
+// lets another thread stop a search. clones share the same flag.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+}
+
+pub struct Progress {
+    pub expanded: usize,
+    pub frontier: usize,
+    // the cost of the batch about to be expanded, which no plan found from
+    // here on can beat.
+    pub cost: f32,
+    // the cost of the cheapest plan queued so far, which may yet be beaten.
+    pub best: Option<f32>,
+}
+
+// breeding, cloning and purifying each take at least a day, so paths
+// popped within this much of each other can't be children of one another.
+const BATCH_WINDOW: f32 = 0.5;
+
+// a path waiting in the search, ordered the way paths are compared: by
+// cost, then preferring the one whose parents took longer, then the one
+// made first, so ties always come out in the same order.
+#[derive(Clone, Copy)]
+struct Queued {
+    cost: f32,
+    parent_cost: f32,
+    id: PathId,
+}
+
+impl Queued {
+    fn new(arena: &Arena, id: PathId) -> Self {
+        let path = &arena[id];
+        Self {
+            cost: path.cost,
+            parent_cost: path.parent_cost,
+            id,
+        }
+    }
+}
+
+// the paths waiting to be expanded, and the cheapest of them that already
+// meets the target.
+struct Frontier {
+    upcoming: BinaryHeap<Reverse<Queued>>,
+    best: Option<Queued>,
+    max_cost: f32,
+}
+
+impl Frontier {
+    fn push(&mut self, arena: &Arena, target: &Target, id: PathId) {
+        let queued = Queued::new(arena, id);
+        if queued.cost > self.max_cost {
+            return;
+        }
+        if target.is_met_by(arena.target(id)) && self.best.is_none_or(|best| queued < best) {
+            self.best = Some(queued);
+        }
+        self.upcoming.push(Reverse(queued));
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let cost = self.cost.partial_cmp(&other.cost).unwrap();
+        if cost != Ordering::Equal {
+            return cost;
+        }
+        let parent_cost = self.parent_cost.partial_cmp(&other.parent_cost).unwrap().reverse();
+        parent_cost.then(self.id.cmp(&other.id))
+    }
+}
+
+// what a search is looking for, and the rules it breeds by.
+#[derive(Clone, Copy)]
+pub struct Problem<'a> {
+    pub target: &'a Target,
+    pub cost_model: &'a CostModel,
+    pub constraints: &'a Constraints,
+    pub context: &'a BreedContext,
+}
+
+// best first search over everything breedable from the sources, cheapest
+// expected time first. returns the first path that meets the target. when
+// the target lists genotypes, a flower that could be one of them may also
+// be purified into it, using the sources of known genotype as testers.
+pub fn search(
+    arena: &mut Arena,
+    sources: &[PathId],
+    target: &Target,
+    cost_model: &CostModel,
+    constraints: &Constraints,
+    context: &BreedContext,
+) -> Option<PathId> {
+    let problem = Problem { target, cost_model, constraints, context };
+    search_with(arena, sources, &problem, &SearchOptions::default()).0
+}
+
+pub fn search_with(arena: &mut Arena, sources: &[PathId], problem: &Problem, options: &SearchOptions) -> (Option<PathId>, SearchStats) {
+    let mut search = Search::new(arena, sources, *problem, *options);
+    let path = search.run(arena, |_, _| {});
+    (path, search.stats)
+}
+
+// how a search ended. why it stopped is in its stats.
+#[derive(Clone, Copy)]
+pub enum Finish {
+    Found(PathId),
+    Exhausted,
+    Stopped,
+}
+
+// the state of a search between batches, as stored in a checkpoint. the
+// order of the frontier comes from the paths themselves, and what else the
+// search keeps can be worked out from these and the sources.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SearchRecord {
+    processed: Vec<PathId>,
+    frontier: Vec<PathId>,
+    best: Option<PathId>,
+    visited: Vec<(DistributionId, bool)>,
+    stats: SearchStats,
+}
+
+// the state of a best first search between batches, so it can be run a
+// batch at a time.
+pub struct Search<'a> {
+    problem: Problem<'a>,
+    options: SearchOptions<'a>,
+    started: Instant,
+    pub stats: SearchStats,
+    testers: Vec<Genotype>,
+    processed: Vec<PathId>,
+    frontier: Frontier,
+    // flowers are visited by their possible genotypes, not their exact
+    // chances, so two ways to the same support only get expanded once. a
+    // flower with a copy is visited apart from one without, since it can be
+    // crossed with itself straight away.
+    visited: HashSet<(Distribution, bool)>,
+    expanded: HashMap<FlowerColor, Vec<PathId>>,
+    // how close in cost paths must be to be expanded together.
+    batch_window: f32,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(arena: &Arena, sources: &[PathId], problem: Problem<'a>, options: SearchOptions<'a>) -> Self {
+        let mut testers: Vec<Genotype> = Vec::new();
+        for &source in sources {
+            let mut genotypes = arena.target(source).genotypes();
+            if let (Some((genotype, _)), None) = (genotypes.next(), genotypes.next()) {
+                if !testers.contains(&genotype) {
+                    testers.push(genotype);
+                }
+            }
+        }
+
+        let mut frontier = Frontier {
+            upcoming: BinaryHeap::new(),
+            best: None,
+            max_cost: options.limits.max_cost.unwrap_or(f32::INFINITY),
+        };
+        for &source in sources {
+            if problem.constraints.allows(arena, &arena[source]) {
+                frontier.push(arena, problem.target, source);
+            }
+        }
+
+        Self {
+            problem,
+            options,
+            started: Instant::now(),
+            stats: SearchStats::default(),
+            testers,
+            processed: Vec::new(),
+            frontier,
+            visited: HashSet::new(),
+            expanded: HashMap::new(),
+            batch_window: BATCH_WINDOW,
+        }
+    }
+
+    // picks up a search from a checkpoint, whose arena must be the one given.
+    pub fn from_record(arena: &Arena, sources: &[PathId], problem: Problem<'a>, options: SearchOptions<'a>, record: &SearchRecord) -> Result<Self, String> {
+        let paths = record.processed.iter().chain(&record.frontier).chain(&record.best);
+        if let Some(id) = paths.copied().find(|id| id.index() >= arena.path_count()) {
+            return Err(format!("the search refers to path {}, which doesn't exist", id.index()));
+        }
+        if let Some((id, _)) = record.visited.iter().find(|(id, _)| id.index() >= arena.distribution_count()) {
+            return Err(format!("the search refers to distribution {}, which doesn't exist", id.index()));
+        }
+
+        let mut search = Self::new(arena, sources, problem, options);
+        search.frontier.upcoming = record.frontier.iter().map(|&id| Reverse(Queued::new(arena, id))).collect();
+        search.frontier.best = record.best.map(|id| Queued::new(arena, id));
+        search.processed = record.processed.clone();
+        for &id in &search.processed {
+            search.expanded.entry(arena.target(id).flower_color).or_default().push(id);
+        }
+        search.visited = record.visited.iter().map(|&(id, single)| (arena[id], single)).collect();
+        search.stats = record.stats.clone();
+        search.stats.stopped = None;
+        Ok(search)
+    }
+
+    pub fn to_record(&self, arena: &Arena) -> SearchRecord {
+        let mut frontier: Vec<_> = self.frontier.upcoming.iter().map(|Reverse(queued)| queued.id).collect();
+        frontier.sort_unstable();
+        let mut visited: Vec<_> = self.visited.iter().map(|(d, single)| (arena.id_of(d).unwrap(), *single)).collect();
+        visited.sort_unstable();
+        SearchRecord {
+            processed: self.processed.clone(),
+            frontier,
+            best: self.frontier.best.map(|best| best.id),
+            visited,
+            stats: self.stats.clone(),
+        }
+    }
+
+    // steps until the search ends, calling between after every batch.
+    // returns the plan found, or the cheapest queued so far if the search
+    // was stopped.
+    pub fn run(&mut self, arena: &mut Arena, mut between: impl FnMut(&Arena, &Self)) -> Option<PathId> {
+        loop {
+            match self.step(arena) {
+                None => between(arena, self),
+                Some(Finish::Found(path)) => return Some(path),
+                Some(Finish::Exhausted) => return None,
+                Some(Finish::Stopped) => return self.best().map(|(path, _)| path),
+            }
+        }
+    }
+
+    // the cheapest queued path that meets the target and its cost. it
+    // isn't known to be the cheapest there is until the search finds it.
+    pub fn best(&self) -> Option<(PathId, f32)> {
+        self.frontier.best.map(|best| (best.id, best.cost))
+    }
+
+    // expands the next batch, or says how the search ended.
+    pub fn step(&mut self, arena: &mut Arena) -> Option<Finish> {
+        let Problem { target, cost_model, constraints, context } = self.problem;
+        let (limits, started, batch_window) = (&self.options.limits, self.started, self.batch_window);
+        let stats = &mut self.stats;
+        let frontier = &mut self.frontier;
+        let first = match frontier.upcoming.peek() {
+            Some(&Reverse(first)) => first,
+            None => return Some(Finish::Exhausted),
+        };
+
+        let memory = arena.memory()
+            + frontier.upcoming.len() * size_of::<Reverse<Queued>>()
+            + (self.processed.len() + self.visited.len()) * size_of::<(Distribution, bool)>();
+        stats.stopped = if self.options.cancel.is_some_and(|c| c.is_cancelled()) {
+            Some(Stop::Cancelled)
+        } else if limits.max_expanded.is_some_and(|max| stats.expanded >= max) {
+            Some(Stop::Expanded)
+        } else if limits.time_limit.is_some_and(|limit| started.elapsed() >= limit) {
+            Some(Stop::Time)
+        } else if limits.max_memory.is_some_and(|max| memory >= max) {
+            Some(Stop::Memory)
+        } else {
+            None
+        };
+        if stats.stopped.is_some() {
+            return Some(Finish::Stopped);
+        }
+        if let Some(progress) = self.options.progress {
+            progress(&Progress {
+                expanded: stats.expanded,
+                frontier: frontier.upcoming.len(),
+                cost: first.cost,
+                best: frontier.best.map(|best| best.cost),
+            });
+        }
+
+        let upcoming = &mut frontier.upcoming;
+        upcoming.pop();
+        // everything about as cheap as the cheapest is expanded together.
+        // the children of a batch all come after it, so this checks the
+        // same flowers in the same order as going one at a time.
+        let mut batch = vec![first.id];
+        let room = limits.max_expanded.map_or(usize::MAX, |max| max - stats.expanded);
+        while batch.len() < room && upcoming.peek().is_some_and(|Reverse(next)| next.cost - first.cost < batch_window) {
+            batch.push(upcoming.pop().unwrap().0.id);
+        }
+
+        let mut new_ids = Vec::new();
+        let mut clones = Vec::new();
+        for new_id in batch {
+            let distribution = *arena.target(new_id);
+            let single = arena[new_id].is_single();
+            let same_color = self.expanded.entry(distribution.flower_color).or_default();
+            if self.options.dominance && same_color.iter().any(|&q| dominates(arena, q, new_id, target)) {
+                stats.dominated += 1;
+                continue;
+            }
+            // a flower with a copy already visited is at least as useful.
+            if self.visited.contains(&(distribution, false)) || !self.visited.insert((distribution, single)) {
+                continue;
+            }
+            if target.is_met_by(&distribution) {
+                return Some(Finish::Found(new_id));
+            }
+            stats.expanded += 1;
+            // crossing a clone with anything but its original's copy is no
+            // quicker than crossing the original, which is already done.
+            if arena[new_id].cloned_from().is_some() {
+                clones.push(new_id);
+                continue;
+            }
+            same_color.push(new_id);
+            new_ids.push(new_id);
+        }
+
+        let mut pairs = Vec::new();
+        for (i, &new_id) in new_ids.iter().enumerate() {
+            pairs.extend(self.processed.iter().chain(&new_ids[..=i]).map(|&p| (new_id, p)));
+        }
+        pairs.extend(clones.iter().map(|&clone| (clone, clone)));
+        remember_crosses(arena, &pairs, context, stats);
+
+        for new_id in new_ids.into_iter().chain(clones) {
+            arena.settle(new_id, cost_model);
+            let is_clone = arena[new_id].cloned_from().is_some();
+            if !is_clone {
+                self.processed.push(new_id);
+            }
+            let partners = if is_clone { std::slice::from_ref(&new_id) } else { &self.processed[..] };
+            let new_target = arena[new_id].target;
+            let mut new_paths = Vec::new();
+            for &p in partners {
+                // the only copy of a flower is crossed with itself through
+                // a clone, which the search makes as a step of its own.
+                if p == new_id && arena[new_id].is_single() {
+                    new_paths.push(Path::clone_of(arena, new_id, cost_model));
+                    continue;
+                }
+                let children = arena.crossed(new_target, arena[p].target).unwrap().to_vec();
+                new_paths.extend(Path::breed(arena, new_id, p, &children, cost_model));
+            }
+            for p in new_paths {
+                if constraints.allows(arena, &arena[p]) {
+                    frontier.push(arena, target, p);
+                }
+            }
+
+            let distribution = *arena.target(new_id);
+            if let Some(genotypes) = &target.genotypes {
+                if distribution.genotype_count() > 1 {
+                    for (g, _) in distribution.genotypes().filter(|(g, _)| genotypes.contains(g)) {
+                        if let Some(purified) = Path::purify(arena, new_id, g, &self.testers, cost_model) {
+                            if constraints.allows(arena, &arena[purified]) {
+                                frontier.push(arena, target, purified);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// how many of the cheapest new flowers each round of a beam search keeps,
+// and how many rounds it goes on for.
+const BEAM_WIDTH: usize = 8;
+const BEAM_ROUNDS: usize = 12;
+
+// a quick search that each round crosses the flowers it kept last round
+// with everything kept so far, then keeps only the cheapest few children.
+// it finds a plan fast, but not necessarily the quickest one, and never
+// purifies.
+pub fn beam_search(arena: &mut Arena, sources: &[PathId], problem: &Problem, stats: &mut SearchStats) -> Option<PathId> {
+    let Problem { target, cost_model, constraints, context } = *problem;
+    let mut seen = HashSet::new();
+    let mut kept: Vec<PathId> = Vec::new();
+    let mut fresh = Vec::new();
+    for &source in sources {
+        if constraints.allows(arena, &arena[source]) && seen.insert(*arena.target(source)) {
+            if target.is_met_by(arena.target(source)) {
+                return Some(source);
+            }
+            fresh.push(source);
+        }
+    }
+
+    let mut best: Option<Queued> = None;
+    for _ in 0..BEAM_ROUNDS {
+        let mut pairs = Vec::new();
+        for (i, &new_id) in fresh.iter().enumerate() {
+            pairs.extend(kept.iter().chain(&fresh[..=i]).map(|&p| (new_id, p)));
+        }
+        for &id in &fresh {
+            arena.settle(id, cost_model);
+        }
+        kept.append(&mut fresh);
+        remember_crosses(arena, &pairs, context, stats);
+
+        let mut candidates = Vec::new();
+        for (left, right) in pairs {
+            let children = arena.crossed(arena[left].target, arena[right].target).unwrap().to_vec();
+            for child in Path::breed(arena, left, right, &children, cost_model) {
+                if !constraints.allows(arena, &arena[child]) {
+                    continue;
+                }
+                let queued = Queued::new(arena, child);
+                if target.is_met_by(arena.target(child)) {
+                    if best.is_none_or(|best| queued < best) {
+                        best = Some(queued);
+                    }
+                } else if !seen.contains(arena.target(child)) {
+                    candidates.push(queued);
+                }
+            }
+        }
+        candidates.sort();
+        for queued in candidates {
+            if fresh.len() == BEAM_WIDTH {
+                break;
+            }
+            if seen.insert(*arena.target(queued.id)) {
+                fresh.push(queued.id);
+            }
+        }
+        // children cost more than their parents, so once everything kept
+        // costs as much as the best plan, nothing bred from it can beat it.
+        if fresh.iter().all(|&f| best.is_some_and(|best| arena[f].cost >= best.cost)) {
+            break;
+        }
+    }
+    best.map(|best| best.id)
+}
+
+// a plan found by an anytime search, and whether it is proven to be the
+// cheapest there is.
+pub struct Plan {
+    pub path: PathId,
+    pub cost: f32,
+    pub proven_optimal: bool,
+}
+
+// gives a plan from a beam search straight away, then runs the full search
+// a batch at a time, giving each plan it queues that is cheaper than the
+// last one given. the full search's own result always comes last, even if
+// it costs more than a beam plan, which can happen since the full search
+// only expands one of the flowers that could be the same genotypes. it is
+// proven optimal whenever the search finishes without dominance pruning,
+// which can skip the flower the cheapest plan needs. it ends early if the
+// search hits a limit or is cancelled.
+pub struct Anytime<'a> {
+    arena: &'a mut Arena,
+    sources: &'a [PathId],
+    problem: Problem<'a>,
+    search: Search<'a>,
+    beam_done: bool,
+    finished: bool,
+    last: Option<f32>,
+}
+
+impl<'a> Anytime<'a> {
+    pub fn new(arena: &'a mut Arena, sources: &'a [PathId], problem: Problem<'a>, options: SearchOptions<'a>) -> Self {
+        let search = Search::new(arena, sources, problem, options);
+        Self {
+            arena,
+            sources,
+            problem,
+            search,
+            beam_done: false,
+            finished: false,
+            last: None,
+        }
+    }
+
+    pub fn arena(&self) -> &Arena {
+        self.arena
+    }
+
+    pub fn stats(&self) -> &SearchStats {
+        &self.search.stats
+    }
+
+    // the plan to give if it's cheaper than the last.
+    fn improvement(&mut self, path: PathId) -> Option<Plan> {
+        let cost = self.arena[path].cost;
+        if self.last.is_some_and(|last| cost >= last) {
+            return None;
+        }
+        self.last = Some(cost);
+        Some(Plan { path, cost, proven_optimal: false })
+    }
+}
+
+impl Iterator for Anytime<'_> {
+    type Item = Plan;
+
+    fn next(&mut self) -> Option<Plan> {
+        if !self.beam_done {
+            self.beam_done = true;
+            if let Some(path) = beam_search(self.arena, self.sources, &self.problem, &mut self.search.stats) {
+                return self.improvement(path);
+            }
+        }
+        while !self.finished {
+            match self.search.step(self.arena) {
+                None => {
+                    if let Some((path, _)) = self.search.best() {
+                        if let Some(plan) = self.improvement(path) {
+                            return Some(plan);
+                        }
+                    }
+                }
+                Some(Finish::Found(path)) => {
+                    self.finished = true;
+                    let cost = self.arena[path].cost;
+                    self.last = Some(cost);
+                    let proven_optimal = !self.search.options.dominance;
+                    return Some(Plan { path, cost, proven_optimal });
+                }
+                Some(_) => self.finished = true,
+            }
+        }
+        None
+    }
+}
+
+// works out every cross among the pairs that hasn't been bred yet, all at
+// once, then remembers them in pair order so ids don't depend on which
+// thread finished first.
+fn remember_crosses(arena: &mut Arena, pairs: &[(PathId, PathId)], context: &BreedContext, stats: &mut SearchStats) {
+    let mut pending = HashSet::new();
+    let mut missing = Vec::new();
+    for &(left, right) in pairs {
+        let (l, r) = (arena[left].target, arena[right].target);
+        if arena.crossed(l, r).is_some() || !pending.insert(unordered(l, r)) {
+            stats.cross_hits += 1;
+        } else {
+            stats.cross_misses += 1;
+            missing.push((left, right));
+        }
+    }
+    let crosses: Vec<_> = missing
+        .par_iter()
+        .map(|&(left, right)| Path::cross(arena, left, right, context))
+        .collect();
+    for (&(left, right), children) in missing.iter().zip(crosses) {
+        let (l, r) = (arena[left].target, arena[right].target);
+        arena.remember_cross(l, r, children);
+    }
+}
+
+// whether a flower already expanded makes this one look useless: it's the
+// same color, could be no genotype this one couldn't, could be the same
+// target genotypes, is more likely to be one of them, and was
+// reached no later. flowers are expanded cheapest first, so it's also no
+// more costly. it mustn't be deeper or rarer either, so generation limits
+// and clones still allow whatever this one would. a color is no reason to
+// prefer either flower, so nothing is pruned for targets without genotypes.
+// this one may still breed something through its other genotypes that the
+// other can't, which is why pruning is only done when asked for.
+fn dominates(arena: &Arena, better: PathId, worse: PathId, target: &Target) -> bool {
+    let genotypes = match &target.genotypes {
+        Some(genotypes) => genotypes,
+        None => return false,
+    };
+    let (b, w) = (arena.target(better), arena.target(worse));
+    if !b.is_within(w) {
+        return false;
+    }
+    let reachable = |d: &Distribution| genotypes.iter().filter(|&&g| d.genotypes().any(|(h, _)| h == g)).count();
+    if reachable(b) != reachable(w) {
+        return false;
+    }
+    let (better, worse) = (&arena[better], &arena[worse]);
+    if better.expected_time > worse.expected_time || better.generation > worse.generation {
+        return false;
+    }
+    if better.is_single() && !worse.is_single() {
+        return false;
+    }
+    // a flower that can't be a target genotype yet says nothing about one
+    // that can't either, so only one that could be is any better.
+    let (b_n, w_n) = (b.numerator_of(genotypes), w.numerator_of(genotypes));
+    if b_n == 0 {
+        return false;
+    }
+    // compare b_n / b_total > w_n / w_total without dividing.
+    b_n as u128 * w.total() as u128 > w_n as u128 * b.total() as u128 || w.is_within(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::Run, path::StepSource};
+
+    const BLUE_ROSE: &str = r#"{
+        "species": "Rose",
+        "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+        "target": { "color": "Blue" }
+    }"#;
+
+    const PURPLE_ROSE: &str = r#"{
+        "species": "Rose",
+        "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+        "target": { "color": "Purple", "genotypes": ["rryywwss", "rryYwwss"] },
+        "constraints": { "excludedColors": ["Black"] }
+    }"#;
+
+    const HYBRID_PURPLE_ROSE: &str = r#"{
+        "species": "Rose",
+        "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+        "target": { "color": "Purple", "genotypes": ["rrYywwss"] }
+    }"#;
+
+    fn plan_cost(json: &str, dominance: bool) -> f32 {
+        plan_cost_and_stats(json, dominance).0
+    }
+
+    fn plan_cost_and_stats(json: &str, dominance: bool) -> (f32, SearchStats) {
+        let mut run = Run::from_json(json);
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let options = SearchOptions { dominance, ..SearchOptions::default() };
+        let (path, stats) = search_with(&mut run.arena, &run.sources, &problem, &options);
+        if run.target.genotypes.is_none() {
+            assert_eq!(stats.dominated, 0);
+        }
+        (run.arena[path.unwrap()].cost, stats)
+    }
+
+    #[test]
+    fn dominance_keeps_the_cheapest_blue_rose() {
+        assert_eq!(plan_cost(BLUE_ROSE, false), 24f32);
+        assert_eq!(plan_cost(BLUE_ROSE, true), plan_cost(BLUE_ROSE, false));
+    }
+
+    // children only get their completion chances once expanded, or the
+    // unlikely ones run the search out of memory.
+    #[test]
+    fn finds_a_blue_rose_by_percentile() {
+        let mut run = Run::from_json(&BLUE_ROSE.replace(r#""target""#, r#""costModel": { "objective": { "percentile": 0.9 } }, "target""#));
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let (path, _) = search_with(&mut run.arena, &run.sources, &problem, &SearchOptions::default());
+        let path = path.unwrap();
+        assert_eq!(run.arena[path].cost, 39f32);
+        assert_eq!(crate::completion::Report::new(&run.arena, path, &run.cost_model).percentile_90, 39);
+        // the queued children far outnumber the expanded flowers, and
+        // keep no chances of their own.
+        assert!(run.arena.completion_days() < run.arena.path_count());
+    }
+
+    // flowers that can't be the target genotype yet mustn't prune each
+    // other, or the plan gets slower.
+    #[test]
+    fn dominance_prunes_but_keeps_the_cheapest_hybrid_purple_rose() {
+        let (cost, stats) = plan_cost_and_stats(HYBRID_PURPLE_ROSE, true);
+        assert!(stats.dominated > 0);
+        assert_eq!(cost, 10f32);
+        assert_eq!(cost, plan_cost(HYBRID_PURPLE_ROSE, false));
+    }
+
+    #[test]
+    fn dominance_keeps_the_cheapest_purple_rose() {
+        assert_eq!(plan_cost(PURPLE_ROSE, true), plan_cost(PURPLE_ROSE, false));
+    }
+
+    // expands one flower at a time when batch_window is zero.
+    fn plan_and_stats(json: &str, dominance: bool, batch_window: f32) -> (String, usize, usize) {
+        let mut run = Run::from_json(json);
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let options = SearchOptions { dominance, ..SearchOptions::default() };
+        let mut search = Search::new(&run.arena, &run.sources, problem, options);
+        search.batch_window = batch_window;
+        let path = search.run(&mut run.arena, |_, _| {}).unwrap();
+        let plan = serde_json::to_string(&crate::arena::InArena(&run.arena, &path)).unwrap();
+        (plan, search.stats.expanded, search.stats.dominated)
+    }
+
+    #[test]
+    fn batches_match_one_at_a_time() {
+        for (json, dominance) in [(BLUE_ROSE, false), (PURPLE_ROSE, true)] {
+            assert_eq!(plan_and_stats(json, dominance, BATCH_WINDOW), plan_and_stats(json, dominance, 0f32));
+        }
+    }
+
+    #[test]
+    fn anytime_proves_its_last_plan() {
+        let mut run = Run::from_json(PURPLE_ROSE);
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let plans: Vec<_> = Anytime::new(&mut run.arena, &run.sources, problem, SearchOptions::default()).collect();
+        let last = plans.last().unwrap();
+        assert!(last.proven_optimal);
+        assert_eq!(last.cost, plan_cost(PURPLE_ROSE, false));
+        assert!(plans[..plans.len() - 1].windows(2).all(|w| w[1].cost < w[0].cost));
+    }
+
+    // a beam plan can be cheaper than the one the full search proves, which
+    // must still be given.
+    #[test]
+    fn anytime_gives_its_proven_plan_even_if_it_costs_more() {
+        let mut run = Run::from_json(PURPLE_ROSE);
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let mut anytime = Anytime::new(&mut run.arena, &run.sources, problem, SearchOptions::default());
+        anytime.beam_done = true;
+        anytime.last = Some(0f32);
+        let plans: Vec<_> = anytime.collect();
+        assert_eq!(plans.len(), 1);
+        assert!(plans[0].proven_optimal);
+        assert_eq!(plans[0].cost, plan_cost(PURPLE_ROSE, false));
+    }
+
+    // the only copy of a flower has to be cloned before it can be crossed
+    // with itself, and the search does that as a step of its own.
+    #[test]
+    fn clones_a_lone_flower_to_cross_it_with_itself() {
+        let mut run = Run::from_json(r#"{
+            "species": "Rose",
+            "flowers": [{ "genotype": "rryyWwss", "copies": 1 }],
+            "target": { "color": "Purple" }
+        }"#);
+        let context = BreedContext::new(run.flower_type);
+        let problem = Problem { target: &run.target, cost_model: &run.cost_model, constraints: &run.constraints, context: &context };
+        let (path, _) = search_with(&mut run.arena, &run.sources, &problem, &SearchOptions::default());
+        let steps = Path::unique_steps(&run.arena, path.unwrap());
+        let sources: Vec<_> = steps.iter().map(|(_, source)| *source).collect();
+        assert_eq!(sources, [StepSource::Owned, StepSource::Clone(0), StepSource::Breed(1, 1)]);
+    }
+}
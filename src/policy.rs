@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use serde::ser::{Serialize, SerializeStruct};
+
+use crate::{arena::{Arena, DistributionId, InArena, PathId}, constraints::Constraints, cost::CostModel, distribution::Distribution, distribution_helpers::BreedContext, flowers::species_name, path::{Path, StepSource}, planner::{Problem, Target, search}};
+
+// a plan that reacts to what actually grows. each node is a single cross
+// between two flowers already owned; whichever color the first child turns
+// out to be decides what to do next.
+//
+// crosses in a policy are carried out one at a time, so its expected time
+// is a sum over steps rather than the max over parents a Path uses.
+#[derive(Clone)]
+pub struct Policy {
+    pub start: Start,
+    pub expected_time: f32,
+    pub outcomes: Vec<Outcome>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Start {
+    Cross(PathId, PathId),
+    // the only copy of a flower is left alone until it spreads, so it can
+    // be crossed with itself. its one outcome is the copy.
+    Clone(PathId),
+    // a flower already owned meets the target, so there is nothing to do.
+    Owned(PathId),
+}
+
+#[derive(Clone)]
+pub struct Outcome {
+    pub probability: f32,
+    pub child: Distribution,
+    pub next: Next,
+}
+
+#[derive(Clone)]
+pub enum Next {
+    // the child is the target.
+    Done,
+    // the child doesn't help, so throw it away and wait for another.
+    Discard,
+    // keep the child and carry on with another cross.
+    Policy(Box<Policy>),
+    // keep the child and follow a fixed plan from here, once the policy is
+    // as deep as it was allowed to get.
+    Plan(PathId),
+}
+
+// what to do next from a set of owned flowers, by the distributions owned
+// and whether each is the only copy, and how many crosses further to look.
+type Memo = HashMap<(Vec<(DistributionId, bool)>, usize), Next>;
+
+impl Policy {
+    // the policy with nothing to do, if an owned flower meets the target.
+    pub fn owned(arena: &Arena, owned: &[PathId], target: &Target) -> Option<Self> {
+        let &met = owned.iter().find(|&&p| target.is_met_by(arena.target(p)))?;
+        Some(Self {
+            start: Start::Owned(met),
+            expected_time: 0f32,
+            outcomes: Vec::new(),
+        })
+    }
+}
+
+// builds a policy from the owned flowers, looking up to depth crosses ahead
+// before falling back to fixed plans. returns None if the target can't be
+// bred. the same flowers are often owned down several branches, in another
+// order, so what to do from each set is only worked out once.
+pub fn build(
+    arena: &mut Arena,
+    owned: &[PathId],
+    target: &Target,
+    cost_model: &CostModel,
+    constraints: &Constraints,
+    depth: usize,
+    context: &BreedContext,
+) -> Option<Policy> {
+    if let Some(policy) = Policy::owned(arena, owned, target) {
+        return Some(policy);
+    }
+    let problem = Problem { target, cost_model, constraints, context };
+    build_memoized(arena, owned, &problem, depth, &mut HashMap::new())
+}
+
+fn build_memoized(arena: &mut Arena, owned: &[PathId], problem: &Problem, depth: usize, memo: &mut Memo) -> Option<Policy> {
+    let Problem { target, cost_model, constraints, context } = *problem;
+    let plan = search(arena, owned, target, cost_model, constraints, context)?;
+    let steps = Path::unique_steps(arena, plan);
+
+    // the earliest cross in the plan only needs flowers that are owned, or
+    // clones of them, which are the same flower for deciding what to do. a
+    // purification starts by crossing the flower with itself.
+    let (left, right) = steps.iter().find_map(|(_, source)| match *source {
+        StepSource::Breed(left, right) => Some((left, right)),
+        StepSource::Purify(original) => Some((original, original)),
+        _ => None,
+    })?;
+    let owned_step = |id: usize| match steps[id].1 {
+        StepSource::Clone(original) => steps[original].0,
+        _ => steps[id].0,
+    };
+    let cross = (owned_step(left), owned_step(right));
+
+    // the only copy of a flower is cloned before it is crossed with itself,
+    // and the copy kept in its place.
+    if cross.0 == cross.1 && arena[cross.0].is_single() {
+        let original = cross.0;
+        let label = arena[original].label().unwrap().to_string();
+        let copy = arena.add(Path::new(label, arena[original].target));
+        let owned: Vec<_> = owned.iter().map(|&p| if p == original { copy } else { p }).collect();
+        let policy = build_memoized(arena, &owned, problem, depth, memo)?;
+        return Some(Policy {
+            start: Start::Clone(original),
+            expected_time: cost_model.clone_days() + policy.expected_time,
+            outcomes: vec![Outcome {
+                probability: 1f32,
+                child: *arena.target(original),
+                next: Next::Policy(Box::new(policy)),
+            }],
+        });
+    }
+
+    let mut outcomes = Vec::new();
+    for (probability, child) in arena.target(cross.0).breed(arena.target(cross.1), context) {
+        let next = if target.is_met_by(&child) {
+            Next::Done
+        } else if owned.iter().any(|&p| *arena.target(p) == child) || !constraints.allows_distribution(&child) {
+            Next::Discard
+        } else {
+            let label = describe(&child);
+            let child_id = arena.intern(child);
+            let mut key: Vec<_> = owned.iter().map(|&p| (arena[p].target, arena[p].is_single())).chain([(child_id, true)]).collect();
+            key.sort_unstable();
+            key.dedup();
+            let key = (key, depth - 1);
+            match memo.get(&key) {
+                Some(next) => next.clone(),
+                None => {
+                    let mut owned = owned.to_vec();
+                    owned.push(arena.add(Path::new_single(label.clone(), child_id)));
+                    let mut constraints = constraints.clone();
+                    if let Some(allowed) = &mut constraints.allowed_sources {
+                        allowed.push(label);
+                    }
+                    let problem = Problem { constraints: &constraints, ..*problem };
+
+                    let next = if depth > 1 {
+                        match build_memoized(arena, &owned, &problem, depth - 1, memo) {
+                            Some(policy) => Next::Policy(Box::new(policy)),
+                            None => Next::Discard,
+                        }
+                    } else {
+                        match search(arena, &owned, target, cost_model, &constraints, context) {
+                            Some(plan) => Next::Plan(plan),
+                            None => Next::Discard,
+                        }
+                    };
+                    memo.insert(key, next.clone());
+                    next
+                }
+            }
+        };
+        outcomes.push(Outcome { probability, child, next });
+    }
+
+    let expected_time = settle(arena, &mut outcomes, cost_model);
+    if !expected_time.is_finite() {
+        return None;
+    }
+
+    Some(Policy {
+        start: Start::Cross(cross.0, cross.1),
+        expected_time,
+        outcomes,
+    })
+}
+
+// picks which outcomes are worth keeping and returns the expected time of
+// the cross.
+fn settle(arena: &Arena, outcomes: &mut [Outcome], cost_model: &CostModel) -> f32 {
+    let times: Vec<_> = outcomes.iter().map(|o| (o.probability, o.next.remaining_time(arena))).collect();
+    let (expected_time, kept) = keep_best(&times, cost_model.breed_days(1f32));
+    for (outcome, kept) in outcomes.iter_mut().zip(kept) {
+        if !kept {
+            outcome.next = Next::Discard;
+        }
+    }
+    expected_time
+}
+
+// given the chance of each outcome of a cross and the time left if its child
+// is kept, works out which children to keep and the expected time of the
+// cross when every other child is discarded and the cross waited on again.
+// a child is only worth keeping if carrying on from it is quicker than
+// discarding it, which is itself what this computes, so try keeping the best
+// k outcomes for every k.
+pub fn keep_best(outcomes: &[(f32, f32)], day: f32) -> (f32, Vec<bool>) {
+    let mut order: Vec<usize> = (0..outcomes.len()).filter(|&i| outcomes[i].1.is_finite()).collect();
+    order.sort_by(|&a, &b| outcomes[a].1.partial_cmp(&outcomes[b].1).unwrap());
+
+    let mut best = (f32::INFINITY, 0);
+    let mut kept_probability = 0f32;
+    let mut kept_time = 0f32;
+    for (k, &i) in order.iter().enumerate() {
+        let (probability, remaining_time) = outcomes[i];
+        kept_probability += probability;
+        kept_time += probability * remaining_time;
+        let expected_time = (day + kept_time) / kept_probability;
+        if expected_time < best.0 {
+            best = (expected_time, k + 1);
+        }
+    }
+
+    let mut kept = vec![false; outcomes.len()];
+    for &i in &order[..best.1] {
+        kept[i] = true;
+    }
+    (best.0, kept)
+}
+
+pub fn describe(distribution: &Distribution) -> String {
+    let genotypes: Vec<_> = distribution.genotypes().map(|(g, _)| format!("{:?}", g)).collect();
+    format!(
+        "{} {} ({})",
+        format!("{:?}", distribution.flower_color).to_lowercase(),
+        species_name(&distribution.flower_type),
+        genotypes.join(", "),
+    )
+}
+
+impl Next {
+    fn remaining_time(&self, arena: &Arena) -> f32 {
+        match self {
+            Next::Done => 0f32,
+            Next::Discard => f32::INFINITY,
+            Next::Policy(policy) => policy.expected_time,
+            Next::Plan(plan) => arena[*plan].expected_time,
+        }
+    }
+}
+
+impl Serialize for InArena<'_, Policy> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        let InArena(arena, this) = *self;
+        let outcomes: Vec<_> = this.outcomes.iter().map(|o| InArena(arena, o)).collect();
+        let mut policy = serializer.serialize_struct("Policy", 3)?;
+        match this.start {
+            Start::Cross(left, right) => policy.serialize_field("cross", &(InArena(arena, &left), InArena(arena, &right)))?,
+            Start::Clone(original) => policy.serialize_field("clone", &InArena(arena, &original))?,
+            Start::Owned(owned) => policy.serialize_field("owned", &InArena(arena, &owned))?,
+        }
+        policy.serialize_field("expectedTime", &this.expected_time)?;
+        policy.serialize_field("outcomes", &outcomes)?;
+        policy.end()
+    }
+}
+
+impl Serialize for InArena<'_, Outcome> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        let InArena(arena, this) = *self;
+        let mut outcome = serializer.serialize_struct("Outcome", 4)?;
+        outcome.serialize_field("color", &this.child.flower_color)?;
+        outcome.serialize_field("probability", &this.probability)?;
+        outcome.serialize_field("genotypes", &this.child)?;
+        match &this.next {
+            Next::Done => outcome.serialize_field("then", "done")?,
+            Next::Discard => outcome.serialize_field("then", "discard")?,
+            Next::Policy(policy) => outcome.serialize_field("then", &InArena(arena, policy.as_ref()))?,
+            Next::Plan(plan) => outcome.serialize_field("then", &InArena(arena, plan))?,
+        }
+        outcome.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Run;
+
+    fn policy(json: &str, depth: usize) -> (Run, Option<Policy>) {
+        let mut run = Run::from_json(json);
+        let context = BreedContext::new(run.flower_type);
+        let policy = build(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, depth, &context);
+        (run, policy)
+    }
+
+    #[test]
+    fn does_nothing_when_the_target_is_owned() {
+        let (run, policy) = policy(r#"{
+            "species": "Rose",
+            "flowers": [{ "seed": "yellow" }, { "seed": "red" }],
+            "target": { "color": "Red" }
+        }"#, 2);
+        let policy = policy.unwrap();
+        assert!(matches!(policy.start, Start::Owned(owned) if owned == run.sources[1]));
+        assert_eq!(policy.expected_time, 0f32);
+        assert!(policy.outcomes.is_empty());
+    }
+
+    #[test]
+    fn looking_further_ahead_never_takes_longer() {
+        let json = r#"{
+            "species": "Rose",
+            "flowers": [{ "seed": "red" }, { "seed": "yellow" }, { "seed": "white" }],
+            "target": { "color": "Purple", "genotypes": ["rryywwss", "rryYwwss"] }
+        }"#;
+        let (_, shallow) = policy(json, 1);
+        let (_, deep) = policy(json, 3);
+        assert!(deep.unwrap().expected_time <= shallow.unwrap().expected_time);
+    }
+
+    // the only copy of a flower has to be cloned before it can be crossed
+    // with itself, and the clone's wait is part of the expected time.
+    #[test]
+    fn clones_a_lone_flower_before_crossing_it_with_itself() {
+        let mut run = Run::from_json(r#"{
+            "species": "Rose",
+            "flowers": [{ "genotype": "rryyWwss", "copies": 1 }],
+            "target": { "color": "Purple" }
+        }"#);
+        let context = BreedContext::new(run.flower_type);
+        let policy = build(&mut run.arena, &run.sources, &run.target, &run.cost_model, &run.constraints, 2, &context).unwrap();
+        assert!(matches!(policy.start, Start::Clone(original) if original == run.sources[0]));
+        let Next::Policy(cross) = &policy.outcomes[0].next else { panic!() };
+        assert!(matches!(cross.start, Start::Cross(left, right) if left == right && !run.arena[left].is_single()));
+        assert_eq!(policy.expected_time, run.cost_model.clone_days() + cross.expected_time);
+    }
+}
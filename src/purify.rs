@@ -0,0 +1,305 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::{Serialize, Serializer};
+
+use crate::{cost::CostModel, distribution::{Distribution, Exact, dist_index_from_genotype, genotype_from_dist_index}, flowers::{FlowerColor, get_color}, genotype::Genotype, policy::keep_best};
+
+// past this many states the rest are treated as dead ends.
+const MAX_STATES: usize = 5_000;
+
+// crossing a flower of uncertain genotype over and over, with a copy of
+// itself or with a tester of known genotype, until it is sure enough to be
+// one genotype. each state is what the current flower could be and whether
+// there is a copy of it. every cross shows the color of a child, which is
+// either kept in place of the current flower or thrown away and the cross
+// waited on again. once those choices are made this is an absorbing markov
+// chain whose absorbing states are the flowers at least
+// cost_model.purify_confidence likely to be the wanted genotype.
+//
+// like the rest of the planner, a flower that is kept is taken to be drawn
+// from its distribution again for every cross, so throwing a child away
+// doesn't narrow down what the current flower is.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Purification {
+    #[serde(serialize_with = "serialize_genotype")]
+    pub genotype: Genotype,
+    pub expected_time: f32,
+    pub states: Vec<PurifyState>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurifyState {
+    pub flower: Distribution,
+    pub copy: bool,
+    pub expected_time: f32,
+    pub cross: Partner,
+    pub outcomes: Vec<PurifyOutcome>,
+    #[serde(skip)]
+    actions: Vec<(Partner, Vec<PurifyOutcome>)>,
+}
+
+// what the current flower is crossed with. cloning isn't a cross, but is
+// what a flower without a copy needs before crossing with itself.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Partner {
+    Clone,
+    Itself,
+    Tester(Genotype),
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurifyOutcome {
+    pub color: FlowerColor,
+    pub probability: f32,
+    pub genotypes: Distribution,
+    // whether the child replaces the current flower.
+    pub keep: bool,
+    // the state after this outcome, or None once the flower is sure enough.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<usize>,
+    // where keeping the child leads.
+    #[serde(skip)]
+    kept: Next,
+}
+
+#[derive(Clone, Copy)]
+enum Next {
+    Done,
+    State(usize),
+    // past the state cap.
+    Unknown,
+}
+
+struct Explorer {
+    genotype: Genotype,
+    confidence: f32,
+    states: Vec<(Distribution, bool)>,
+    // states are the same only if every chance is, not just which
+    // genotypes are possible.
+    ids: HashMap<(Exact, bool), usize>,
+    queue: VecDeque<usize>,
+}
+
+impl Purification {
+    // the fastest way to purify the flower into the genotype, or None if it
+    // already is that genotype or never can be. copy says whether there is
+    // already a second one of the flower to cross it with.
+    pub fn new(
+        flower: &Distribution,
+        genotype: Genotype,
+        testers: &[Genotype],
+        copy: bool,
+        cost_model: &CostModel,
+    ) -> Option<Self> {
+        let mut explorer = Explorer {
+            genotype,
+            confidence: cost_model.purify_confidence,
+            states: Vec::new(),
+            ids: HashMap::new(),
+            queue: VecDeque::new(),
+        };
+        if let Next::Done = explorer.intern(*flower, copy) {
+            return None;
+        }
+
+        // states are explored in the order they were numbered.
+        let mut states = Vec::new();
+        while let Some(state) = explorer.queue.pop_front() {
+            let (flower, copy) = explorer.states[state];
+            let mut partners = vec![if copy { Partner::Itself } else { Partner::Clone }];
+            partners.extend(testers.iter().map(|&t| Partner::Tester(t)));
+
+            let mut actions = Vec::new();
+            for partner in partners {
+                let outcomes = match partner {
+                    Partner::Clone => vec![PurifyOutcome {
+                        color: flower.flower_color,
+                        probability: 1f32,
+                        genotypes: flower,
+                        keep: true,
+                        next: None,
+                        kept: explorer.intern(flower, true),
+                    }],
+                    _ => cross(&flower, partner).into_iter().map(|(probability, child)| PurifyOutcome {
+                        color: child.flower_color,
+                        probability,
+                        genotypes: child,
+                        keep: false,
+                        next: None,
+                        kept: explorer.intern(child, false),
+                    }).collect(),
+                };
+                actions.push((partner, outcomes));
+            }
+            states.push(PurifyState {
+                flower,
+                copy,
+                expected_time: f32::INFINITY,
+                cross: actions[0].0,
+                outcomes: Vec::new(),
+                actions,
+            });
+        }
+
+        // values only ever go down from infinity, so this settles.
+        loop {
+            let mut changed = false;
+            for s in 0..states.len() {
+                for a in 0..states[s].actions.len() {
+                    let (partner, outcomes) = &states[s].actions[a];
+                    let remaining: Vec<_> = outcomes.iter().map(|o| (o.probability, remaining_time(&states, o.kept))).collect();
+                    let (value, kept) = match partner {
+                        // the copy always turns up eventually.
+                        Partner::Clone => (cost_model.clone_days() + remaining[0].1, vec![true]),
+                        _ => keep_best(&remaining, cost_model.breed_days(1f32)),
+                    };
+                    if value < states[s].expected_time - 1e-4 {
+                        changed = true;
+                    }
+                    if value < states[s].expected_time {
+                        let state = &mut states[s];
+                        state.expected_time = value;
+                        state.cross = state.actions[a].0;
+                        state.outcomes = state.actions[a].1.clone();
+                        for (outcome, keep) in state.outcomes.iter_mut().zip(kept) {
+                            outcome.keep = keep;
+                            outcome.next = match (keep, outcome.kept) {
+                                (false, _) => Some(s),
+                                (true, Next::State(next)) => Some(next),
+                                (true, _) => None,
+                            };
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let expected_time = states[0].expected_time;
+        if !expected_time.is_finite() {
+            return None;
+        }
+        Some(Self {
+            genotype,
+            expected_time,
+            states,
+        })
+    }
+
+    // the testers it was worked out with, which every state lists in the
+    // same order after crossing with the flower's copy.
+    pub fn testers(&self) -> Vec<Genotype> {
+        self.states[0].actions.iter().filter_map(|(partner, _)| match partner {
+            Partner::Tester(tester) => Some(*tester),
+            _ => None,
+        }).collect()
+    }
+
+    // what to do with a child of the given genotype: whether to keep it,
+    // and the state that leads to, or None once the flower is sure enough.
+    pub fn step(&self, state: usize, child: Genotype) -> (bool, Option<usize>) {
+        let color = get_color(&self.states[state].flower.flower_type, child);
+        let outcome = self.states[state].outcomes.iter().find(|o| o.color == color).unwrap();
+        (outcome.keep, outcome.next)
+    }
+}
+
+fn remaining_time(states: &[PurifyState], next: Next) -> f32 {
+    match next {
+        Next::Done => 0f32,
+        Next::State(next) => states[next].expected_time,
+        Next::Unknown => f32::INFINITY,
+    }
+}
+
+impl Explorer {
+    fn intern(&mut self, flower: Distribution, copy: bool) -> Next {
+        let chance = flower.genotypes().find(|&(g, _)| g == self.genotype).map_or(0f32, |(_, p)| p);
+        if chance >= self.confidence {
+            return Next::Done;
+        }
+        let key = (Exact(flower), copy);
+        if let Some(&id) = self.ids.get(&key) {
+            return Next::State(id);
+        }
+        if self.states.len() >= MAX_STATES {
+            return Next::Unknown;
+        }
+        self.ids.insert(key, self.states.len());
+        self.queue.push_back(self.states.len());
+        self.states.push((flower, copy));
+        Next::State(self.states.len() - 1)
+    }
+}
+
+// the children of the flower and the partner, split by color. a flower
+// crossed with its copy always has the same genotype on both sides, unlike
+// two flowers that merely share a distribution.
+fn cross(flower: &Distribution, partner: Partner) -> Vec<(f32, Distribution)> {
+    let flower_type = flower.flower_type;
+    let mut by_color: BTreeMap<usize, (FlowerColor, BTreeMap<usize, u128>)> = BTreeMap::new();
+    for (index, weight) in flower.numerators() {
+        let genotype = genotype_from_dist_index(index);
+        let other = match partner {
+            Partner::Tester(tester) => tester,
+            _ => genotype,
+        };
+        for (numerator, child) in genotype.breed(&other) {
+            let color = get_color(&flower_type, child);
+            let (_, children) = by_color.entry(color as usize).or_insert((color, BTreeMap::new()));
+            *children.entry(dist_index_from_genotype(child)).or_insert(0) += weight as u128 * numerator as u128;
+        }
+    }
+
+    let total = flower.total() as f64 * 256f64;
+    by_color.into_values().map(|(flower_color, children)| {
+        let probability = children.values().sum::<u128>() as f64 / total;
+        let weights: Vec<_> = children.into_iter().collect();
+        (probability as f32, Distribution::from_weights(flower_type, flower_color, &weights))
+    }).collect()
+}
+
+impl Serialize for Partner {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Partner::Clone => serializer.serialize_str("clone"),
+            Partner::Itself => serializer.serialize_str("itself"),
+            Partner::Tester(tester) => serialize_genotype(tester, serializer),
+        }
+    }
+}
+
+fn serialize_genotype<S: Serializer>(genotype: &Genotype, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:?}", genotype))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flowers::FlowerType, input::parse_genotype};
+
+    fn purify(flower_type: FlowerType, color: FlowerColor, genotype: &str) -> Option<f32> {
+        let flower = Distribution::from_color(flower_type, color).unwrap();
+        let genotype = parse_genotype(&flower_type, genotype).unwrap();
+        Purification::new(&flower, genotype, &[], true, &CostModel::default()).map(|p| p.expected_time)
+    }
+
+    // states with the same possible genotypes but different chances must
+    // be kept apart, or these look impossible.
+    #[test]
+    fn purifies_a_red_lily() {
+        let days = purify(FlowerType::Lily, FlowerColor::Red, "rrYYWWss").unwrap();
+        assert!((days - 12.5).abs() < 0.01, "{}", days);
+    }
+
+    #[test]
+    fn purifies_an_orange_rose() {
+        let days = purify(FlowerType::Rose, FlowerColor::Orange, "RRYYWWss").unwrap();
+        assert!((days - 15.9).abs() < 0.05, "{}", days);
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::Serialize;
+
+use crate::{arena::{Arena, PathId}, cost::CostModel, distribution::Distribution, flowers::{FlowerColor, GOLD_CHANCE, can_turn_gold, genotype_color, get_color}, genotype::Genotype, purify::{Partner, Purification}};
+
+// a purification that hasn't finished after this long never will.
+const MAX_PURIFY_DAYS: u64 = 10_000;
+
+// how long a plan took over many simulated runs, next to what the planner
+// expected. runs where a parent turned out unable to ever give the wanted
+// color are counted as unfinished and left out of the statistics.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub runs: usize,
+    pub unfinished: usize,
+    pub expected_time: f32,
+    // left out when no run finished.
+    #[serde(flatten)]
+    pub days: Option<Days>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Days {
+    pub mean: f64,
+    pub median: f64,
+    pub variance: f64,
+    pub percentile_90: f64,
+}
+
+// plays a plan out day by day. every flower gets an actual genotype, sampled
+// from its distribution for starting flowers and from its parents' cross for
+// bred ones, and each day a pair has breed_rate chance of producing a child.
+// a clone has the genotype of its original and shows up with clone_rate
+// chance each day, and a purification follows its keep or discard choices.
+// a step used more than once is the same flower each time.
+pub fn simulate(arena: &Arena, id: PathId, cost_model: &CostModel, runs: usize, seed: u64) -> Report {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut days: Vec<f64> = (0..runs)
+        .filter_map(|_| run(arena, id, cost_model, &mut rng, &mut HashMap::new()))
+        .map(|(day, _)| day as f64)
+        .collect();
+    days.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let finished = days.len();
+    Report {
+        runs,
+        unfinished: runs - finished,
+        expected_time: arena[id].expected_time,
+        days: if finished > 0 { Some(Days::new(&days)) } else { None },
+    }
+}
+
+impl Days {
+    // from the days of at least one finished run, in increasing order.
+    fn new(sorted: &[f64]) -> Self {
+        let finished = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / finished as f64;
+        let variance = if finished > 1 {
+            sorted.iter().map(|d| (d - mean) * (d - mean)).sum::<f64>() / (finished - 1) as f64
+        } else {
+            0f64
+        };
+        Self {
+            mean,
+            median: percentile(sorted, 0.5),
+            variance,
+            percentile_90: percentile(sorted, 0.9),
+        }
+    }
+}
+
+// the day the flower was obtained and its genotype, or None if its parents
+// can never produce its color.
+fn run<R: Rng>(
+    arena: &Arena,
+    id: PathId,
+    cost_model: &CostModel,
+    rng: &mut R,
+    known: &mut HashMap<PathId, Option<(u64, Genotype)>>,
+) -> Option<(u64, Genotype)> {
+    if let Some(&result) = known.get(&id) {
+        return result;
+    }
+    let result = grow(arena, id, cost_model, rng, known);
+    known.insert(id, result);
+    result
+}
+
+fn grow<R: Rng>(
+    arena: &Arena,
+    id: PathId,
+    cost_model: &CostModel,
+    rng: &mut R,
+    known: &mut HashMap<PathId, Option<(u64, Genotype)>>,
+) -> Option<(u64, Genotype)> {
+    let path = &arena[id];
+    let target = arena.target(id);
+    if let Some(original) = path.cloned_from() {
+        let (day, genotype) = run(arena, original, cost_model, rng, known)?;
+        return Some((day + wait(cost_model.clone_rate, rng), genotype));
+    }
+    if let Some((original, purification)) = path.purified_from() {
+        let (day, genotype) = run(arena, original, cost_model, rng, known)?;
+        return purify(day, genotype, purification, cost_model, rng);
+    }
+    let (left, right) = match path.parents() {
+        Some(parents) => parents,
+        None => return Some((0, sample(target, rng))),
+    };
+    let (left_day, left_genotype) = run(arena, left, cost_model, rng, known)?;
+    let (right_day, right_genotype) = run(arena, right, cost_model, rng, known)?;
+    let breed_rate = cost_model.breed_rate as f64;
+
+    let flower_type = target.flower_type;
+    let wanted = genotype_color(&flower_type, target.flower_color);
+    // gold children are black ones that turned gold, which only happens when
+    // both parents really are black roses.
+    let gold = target.flower_color == FlowerColor::Gold;
+    if gold && !(can_turn_gold(&flower_type, left_genotype) && can_turn_gold(&flower_type, right_genotype)) {
+        return None;
+    }
+    let children: Vec<_> = left_genotype.breed(&right_genotype).collect();
+    if children.iter().all(|(_, g)| get_color(&flower_type, *g) != wanted) {
+        return None;
+    }
+
+    let mut day = left_day.max(right_day);
+    loop {
+        day += 1;
+        if !rng.gen_bool(breed_rate) {
+            continue;
+        }
+        let mut roll = rng.gen_range(0..256);
+        for &(numerator, genotype) in &children {
+            if roll < numerator {
+                if get_color(&flower_type, genotype) == wanted && (!gold || rng.gen_bool(GOLD_CHANCE as f64)) {
+                    return Some((day, genotype));
+                }
+                break;
+            }
+            roll -= numerator;
+        }
+    }
+}
+
+// follows a purification with a flower of known genotype, which may turn
+// out to be one that never gets there. the flower it ends with is only
+// likely to be the wanted genotype, so it may be something else.
+fn purify<R: Rng>(
+    mut day: u64,
+    mut genotype: Genotype,
+    purification: &Purification,
+    cost_model: &CostModel,
+    rng: &mut R,
+) -> Option<(u64, Genotype)> {
+    if purification.states.is_empty() {
+        return Some((day, genotype));
+    }
+    let mut state = 0;
+    let give_up = day + MAX_PURIFY_DAYS;
+    while day < give_up {
+        let partner = match purification.states[state].cross {
+            Partner::Clone => {
+                day += wait(cost_model.clone_rate, rng);
+                state = purification.states[state].outcomes[0].next.unwrap();
+                continue;
+            }
+            Partner::Itself => genotype,
+            Partner::Tester(tester) => tester,
+        };
+        day += wait(cost_model.breed_rate, rng);
+        let mut roll = rng.gen_range(0..256);
+        let mut child = genotype;
+        for (numerator, g) in genotype.breed(&partner) {
+            if roll < numerator {
+                child = g;
+                break;
+            }
+            roll -= numerator;
+        }
+        let (keep, next) = purification.step(state, child);
+        if keep {
+            genotype = child;
+        }
+        match next {
+            None => return Some((day, genotype)),
+            Some(next) => state = next,
+        }
+    }
+    None
+}
+
+// days until something with this daily chance happens.
+fn wait<R: Rng>(chance: f32, rng: &mut R) -> u64 {
+    let mut days = 1;
+    while !rng.gen_bool(chance as f64) {
+        days += 1;
+    }
+    days
+}
+
+fn sample<R: Rng>(distribution: &Distribution, rng: &mut R) -> Genotype {
+    let mut roll = rng.gen::<f32>();
+    let mut last = None;
+    for (genotype, probability) in distribution.genotypes() {
+        if roll < probability {
+            return genotype;
+        }
+        roll -= probability;
+        last = Some(genotype);
+    }
+    last.unwrap()
+}
+
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    let rank = (fraction * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flowers::FlowerType, input::Run, path::Path};
+
+    const WHITE_SEEDS: &str = r#"{
+        "species": "Rose",
+        "flowers": [{ "seed": "white" }],
+        "target": { "color": "Purple" }
+    }"#;
+
+    #[test]
+    fn reports_the_days_of_finished_runs() {
+        let mut run = Run::from_json(WHITE_SEEDS);
+        let purple = run.arena.intern(Distribution::from_color(FlowerType::Rose, FlowerColor::Purple).unwrap());
+        let child = Path::breed(&mut run.arena, run.sources[0], run.sources[0], &[(0.25, purple)], &run.cost_model)[0];
+        let report = simulate(&run.arena, child, &run.cost_model, 100, 0);
+        assert_eq!(report.unfinished, 0);
+        assert!(report.days.unwrap().mean >= 1f64);
+    }
+
+    // white seeds never give a red child.
+    #[test]
+    fn reports_no_days_when_no_run_finishes() {
+        let mut run = Run::from_json(WHITE_SEEDS);
+        let red = run.arena.intern(Distribution::from_color(FlowerType::Rose, FlowerColor::Red).unwrap());
+        let child = Path::breed(&mut run.arena, run.sources[0], run.sources[0], &[(0.25, red)], &run.cost_model)[0];
+        let report = simulate(&run.arena, child, &run.cost_model, 100, 0);
+        assert_eq!(report.unfinished, 100);
+        assert!(report.days.is_none());
+        assert!(!serde_json::to_string(&report).unwrap().contains("mean"));
+    }
+}